@@ -0,0 +1,494 @@
+/// 音频编码设置：编码器、码率、采样率与声道数，按输出容器/协议校验并映射为 FFmpeg 参数
+use std::fmt;
+use std::str::FromStr;
+
+use crate::bitrate::{Bitrate, DEFAULT_AUDIO_BITRATE};
+use crate::error::RecorderError;
+use crate::filter_graph::{FilterGraph, Pad};
+use crate::level_meter;
+use crate::validation::ValidationReport;
+
+/// 允许的采样率
+pub const SAMPLE_RATES: [u32; 2] = [44_100, 48_000];
+
+/// 按时间戳异步重采样：每秒最多拉伸/压缩 1000 个采样，补偿声卡时钟相对视频时钟的漂移；
+/// `first_pts=0` 使音频从 0 开始，晚于视频时补静音，早于视频时裁掉多余部分
+const SYNC_FILTER: &str = "aresample=async=1000:first_pts=0";
+
+/// 音频源音量倍数上限
+pub const MAX_VOLUME: f64 = 10.0;
+
+/// `--av-offset` 允许的最大绝对值（秒）
+pub const MAX_AV_OFFSET_SECONDS: f64 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioCodec {
+    Aac,
+    /// Opus (libopus)，仅支持 48 kHz
+    Opus,
+    /// MP3 (libmp3lame)
+    Mp3,
+    /// 无损压缩，忽略码率
+    Flac,
+    /// 16 位 PCM，未压缩，忽略码率
+    Pcm,
+}
+
+impl AudioCodec {
+    pub const ALL: [AudioCodec; 5] = [
+        AudioCodec::Aac,
+        AudioCodec::Opus,
+        AudioCodec::Mp3,
+        AudioCodec::Flac,
+        AudioCodec::Pcm,
+    ];
+
+    /// 对应的 FFmpeg 编码器名称（-c:a 参数）
+    pub fn encoder(&self) -> &'static str {
+        match self {
+            AudioCodec::Aac => "aac",
+            AudioCodec::Opus => "libopus",
+            AudioCodec::Mp3 => "libmp3lame",
+            AudioCodec::Flac => "flac",
+            AudioCodec::Pcm => "pcm_s16le",
+        }
+    }
+
+    /// 命令行可接受的名称（首个为推荐写法）
+    fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            AudioCodec::Aac => &["aac"],
+            AudioCodec::Opus => &["opus", "libopus"],
+            AudioCodec::Mp3 => &["mp3", "libmp3lame"],
+            AudioCodec::Flac => &["flac"],
+            AudioCodec::Pcm => &["pcm", "pcm_s16le", "wav"],
+        }
+    }
+
+    pub fn is_lossless(&self) -> bool {
+        matches!(self, AudioCodec::Flac | AudioCodec::Pcm)
+    }
+
+    /// 有损编码器可用的码率范围 (kbit/s)，按声道总和计
+    fn bitrate_range_kbps(&self) -> (u64, u64) {
+        match self {
+            AudioCodec::Aac => (32, 512),
+            AudioCodec::Opus => (6, 510),
+            AudioCodec::Mp3 => (32, 320),
+            AudioCodec::Flac | AudioCodec::Pcm => (0, u64::MAX),
+        }
+    }
+
+    fn supports_sample_rate(&self, rate: u32) -> bool {
+        match self {
+            AudioCodec::Opus => rate == 48_000,
+            _ => SAMPLE_RATES.contains(&rate),
+        }
+    }
+
+    /// 未指定采样率时使用的值（与原先硬编码的 44.1 kHz 保持一致，Opus 只能用 48 kHz）
+    fn default_sample_rate(&self) -> u32 {
+        match self {
+            AudioCodec::Opus => 48_000,
+            _ => 44_100,
+        }
+    }
+}
+
+impl fmt::Display for AudioCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.aliases()[0])
+    }
+}
+
+impl FromStr for AudioCodec {
+    type Err = RecorderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        AudioCodec::ALL
+            .into_iter()
+            .find(|c| c.aliases().contains(&lower.as_str()))
+            .ok_or_else(|| RecorderError::UnsupportedAudioCodec(s.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioSettings {
+    /// 音频编码器，未指定时按输出容器选择（webm 为 opus，其余为 aac）
+    pub codec: Option<AudioCodec>,
+    /// 有损编码的码率
+    pub bitrate: Bitrate,
+    /// 采样率，未指定时按编码器选择
+    pub sample_rate: Option<u32>,
+    /// 声道数（1 = 单声道，2 = 立体声）
+    pub channels: u32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            codec: None,
+            bitrate: DEFAULT_AUDIO_BITRATE,
+            sample_rate: None,
+            channels: 2,
+        }
+    }
+}
+
+impl AudioSettings {
+    /// 检查码率、采样率与声道数（容器兼容性由 RecorderConfig 检查）
+    pub fn validate(&self, codec: AudioCodec, report: &mut ValidationReport) {
+        let (min, max) = codec.bitrate_range_kbps();
+        let kbps = self.bitrate.kbps();
+        if !codec.is_lossless() && (kbps < min as f64 || kbps > max as f64) {
+            report
+                .error("audio_bitrate", self.bitrate, format!("超出 {} 的码率范围", codec))
+                .suggest(format!("取 {}k - {}k 之间的值", min, max));
+        }
+
+        if let Some(rate) = self.sample_rate {
+            if !codec.supports_sample_rate(rate) {
+                let issue = report.error("sample_rate", rate, format!("{} 不支持该采样率", codec));
+                if codec == AudioCodec::Opus {
+                    issue.suggest("opus 只能使用 48000");
+                } else {
+                    issue.suggest("可选 44100, 48000");
+                }
+            }
+        }
+
+        if !(1..=2).contains(&self.channels) {
+            report
+                .error("channels", self.channels, "不支持的声道数")
+                .suggest("1 为单声道，2 为立体声");
+        }
+    }
+
+    /// 生成音频编码参数
+    pub fn encoder_args(&self, codec: AudioCodec) -> Vec<String> {
+        let mut args = vec!["-c:a".to_string(), codec.encoder().to_string()];
+        if !codec.is_lossless() {
+            args.extend(["-b:a".to_string(), self.bitrate.to_string()]);
+        }
+        let rate = self.sample_rate.unwrap_or_else(|| codec.default_sample_rate());
+        args.extend(["-ar".to_string(), rate.to_string()]);
+        args.extend(["-ac".to_string(), self.channels.to_string()]);
+        args
+    }
+}
+
+/// 平台对应的 FFmpeg 音频输入设备类型
+pub fn input_format() -> &'static str {
+    #[cfg(windows)]
+    {
+        "dshow"
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        "pulse"
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        "avfoundation"
+    }
+
+    #[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+    {
+        "unknown"
+    }
+}
+
+/// `ffmpeg -sources` 列出的音频输入设备
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioDevice {
+    /// 设备名称（即 --audio-device 接受的值）
+    pub name: String,
+    pub description: String,
+    /// 是否为系统默认输入
+    pub is_default: bool,
+}
+
+impl AudioDevice {
+    /// PulseAudio 为每个输出设备提供一个 `.monitor` 输入，用于采集系统声音
+    pub fn is_monitor(&self) -> bool {
+        self.name.ends_with(".monitor")
+    }
+}
+
+/// 设备列表的文本表格：默认设备以 `*` 标记，monitor 注明可采集系统声音
+pub fn device_table(devices: &[AudioDevice]) -> String {
+    let width = devices.iter().map(|d| d.name.chars().count()).max().unwrap_or(0);
+    devices
+        .iter()
+        .map(|d| {
+            format!(
+                "{} {:<width$}  {}{}",
+                if d.is_default { "*" } else { " " },
+                d.name,
+                d.description,
+                if d.is_monitor() { "（系统声音）" } else { "" },
+                width = width
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 在设备列表中查找设备名称，支持 PulseAudio 的 `default` 与 `@DEFAULT_*@` 特殊名称
+pub fn device_exists(devices: &[AudioDevice], name: &str) -> bool {
+    match name {
+        "default" | "@DEFAULT_SOURCE@" => !devices.is_empty(),
+        "@DEFAULT_MONITOR@" => devices.iter().any(AudioDevice::is_monitor),
+        _ => devices.iter().any(|d| d.name == name),
+    }
+}
+
+/// 音频源类型
+#[derive(Debug, Clone, PartialEq)]
+pub enum AudioSourceKind {
+    /// 麦克风等输入设备，None 表示系统默认输入
+    Microphone(Option<String>),
+    /// 系统播放的声音（Linux：PulseAudio 默认输出设备的 monitor）
+    System,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioSource {
+    pub kind: AudioSourceKind,
+    /// 音量倍数，1.0 为原始音量
+    pub volume: f64,
+    /// 分轨输出时的音轨标题，未指定时使用音频源名称
+    pub title: Option<String>,
+    /// 分轨输出时的音轨语言（ISO 639-2 三字母代码，例如 chi、eng）
+    pub language: Option<String>,
+}
+
+impl AudioSource {
+    /// 平台对应的设备名称
+    pub fn device(&self) -> String {
+        match &self.kind {
+            AudioSourceKind::Microphone(Some(name)) => name.clone(),
+            AudioSourceKind::Microphone(None) => {
+                if cfg!(windows) {
+                    "Microphone".to_string()
+                } else if cfg!(target_os = "macos") {
+                    "0".to_string()
+                } else {
+                    "default".to_string()
+                }
+            }
+            // PulseAudio 的特殊名称，始终指向当前默认输出设备的 monitor
+            AudioSourceKind::System => "@DEFAULT_MONITOR@".to_string(),
+        }
+    }
+
+    /// 用于日志与校验报告的名称
+    pub fn label(&self) -> &'static str {
+        match self.kind {
+            AudioSourceKind::Microphone(_) => "麦克风",
+            AudioSourceKind::System => "系统声音",
+        }
+    }
+
+    /// FFmpeg 输入参数（-f 与 -i），`input_format` 为 dshow / pulse / avfoundation
+    pub fn input_args(&self, input_format: &str) -> Vec<String> {
+        let device = self.device();
+        let input = match input_format {
+            "dshow" => format!("audio={}", device),
+            "avfoundation" => format!(":{}", device),
+            _ => device,
+        };
+        vec!["-f".to_string(), input_format.to_string(), "-i".to_string(), input]
+    }
+
+    pub fn title(&self) -> &str {
+        self.title.as_deref().unwrap_or(self.label())
+    }
+
+    /// 检查音轨语言代码是否为 ISO 639-2 格式（MP4 要求三字母代码）
+    pub fn validate(&self, report: &mut ValidationReport) {
+        if let Some(lang) = &self.language {
+            if lang.len() != 3 || !lang.chars().all(|c| c.is_ascii_lowercase()) {
+                report
+                    .error("audio_language", lang, format!("{}音轨的语言代码无效", self.label()))
+                    .suggest("使用 ISO 639-2 三字母代码，例如 chi、eng、jpn、und");
+            }
+        }
+        if self.title.as_deref().is_some_and(|t| t.trim().is_empty()) {
+            report
+                .error("audio_title", "", format!("{}音轨的标题为空", self.label()))
+                .suggest("去掉该参数以使用默认标题");
+        }
+    }
+
+    fn has_volume(&self) -> bool {
+        (self.volume - 1.0).abs() > f64::EPSILON
+    }
+
+    pub fn is_microphone(&self) -> bool {
+        matches!(self.kind, AudioSourceKind::Microphone(_))
+    }
+
+    /// 第 `index` 个音频源的滤镜链：麦克风处理（如有）+ 音量调整（如有）+ 电平统计（如有）+ 漂移补偿重采样
+    ///
+    /// 电平统计位于音量调整之后，测得的是实际写入的电平。需要运行时控制时，
+    /// 音量滤镜始终存在并命名为 `volume@src<序号>`，以便接收 FFmpeg 的滤镜命令。
+    fn filter_chain(&self, index: usize, options: &ChainOptions) -> String {
+        let mut chain: Vec<String> = Vec::new();
+        if let Some(filters) = options.mic_filters.filter(|_| self.is_microphone()) {
+            chain.push(filters.to_string());
+        }
+        if options.runtime_volume {
+            chain.push(format!("{}=volume={}", volume_instance(index), self.volume));
+        } else if self.has_volume() {
+            chain.push(format!("volume={}", self.volume));
+        }
+        if options.metering {
+            chain.push(level_meter::meter_filter(index));
+        }
+        chain.push(SYNC_FILTER.to_string());
+        chain.join(",")
+    }
+}
+
+/// 运行时可调音量滤镜的实例名
+pub fn volume_instance(index: usize) -> String {
+    format!("volume@src{}", index)
+}
+
+/// 逐个音频源滤镜链的可选部分
+#[derive(Debug, Clone, Default)]
+pub struct ChainOptions<'a> {
+    /// 麦克风处理链（高通、降噪等），只作用于麦克风
+    pub mic_filters: Option<&'a str>,
+    /// 插入电平统计
+    pub metering: bool,
+    /// 音量可在运行时通过滤镜命令调整
+    pub runtime_volume: bool,
+}
+
+/// 多个音频源的输出方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AudioLayout {
+    /// 用 amix 混合为一条音轨
+    #[default]
+    Mix,
+    /// 每个音频源单独一条音轨
+    Separate,
+}
+
+/// 每条音轨的标题、语言与默认轨标记，仅在音轨与音频源一一对应时使用
+///
+/// MP4/MOV 播放器多以 handler_name 作为音轨名称，`isobmff` 为 true 时同时写入。
+pub fn track_metadata_args(sources: &[AudioSource], isobmff: bool) -> Vec<String> {
+    let mut args = Vec::new();
+    for (i, source) in sources.iter().enumerate() {
+        let stream = format!("-metadata:s:a:{}", i);
+        args.extend([stream.clone(), format!("title={}", source.title())]);
+        if isobmff {
+            args.extend([stream.clone(), format!("handler_name={}", source.title())]);
+        }
+        if let Some(lang) = &source.language {
+            args.extend([stream, format!("language={}", lang)]);
+        }
+        // 第一条音轨为默认播放音轨
+        args.extend([format!("-disposition:a:{}", i), if i == 0 { "default" } else { "0" }.to_string()]);
+    }
+    args
+}
+
+/// 将全部音频源加入滤镜图（逐路音量/重采样，混音时再经 amix），返回输出端口
+///
+/// `first_input` 为第一个音频输入的序号；混音时返回一个端口，否则每个音频源一个端口，顺序即音轨顺序。
+pub fn compose(
+    sources: &[AudioSource],
+    layout: AudioLayout,
+    first_input: usize,
+    options: &ChainOptions,
+    graph: &mut FilterGraph,
+) -> Vec<Pad> {
+    let tracks: Vec<Pad> = sources
+        .iter()
+        .enumerate()
+        .map(|(i, s)| graph.link(&[&Pad::audio_input(first_input + i)], &[s.filter_chain(i, options)], "a"))
+        .collect();
+    if layout == AudioLayout::Separate || tracks.len() < 2 {
+        return tracks;
+    }
+    // normalize=0 避免 amix 按输入数量衰减音量
+    let inputs: Vec<&Pad> = tracks.iter().collect();
+    let amix = format!("amix=inputs={}:duration=longest:normalize=0", tracks.len());
+    vec![graph.link(&inputs, &[amix], "aout")]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+    use crate::ffmpeg_encoder::push_graph;
+
+    fn sources() -> Vec<AudioSource> {
+        vec![
+            AudioSource { kind: AudioSourceKind::Microphone(None), volume: 1.5, title: None, language: None },
+            AudioSource { kind: AudioSourceKind::System, volume: 1.0, title: None, language: None },
+        ]
+    }
+
+    /// 组合音频并输出映射，返回 `-filter_complex` 之后的全部参数
+    fn mapped(layout: AudioLayout) -> Vec<String> {
+        let mut graph = FilterGraph::default();
+        let options = ChainOptions { mic_filters: Some("highpass=f=80"), ..Default::default() };
+        let audio = compose(&sources(), layout, 1, &options, &mut graph);
+        let mut cmd = Command::new("ffmpeg");
+        push_graph(&mut cmd, &graph, Some(&Pad::video_input(0)), &audio);
+        cmd.get_args().map(|a| a.to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn mix_maps_single_amix_output() {
+        assert_eq!(
+            mapped(AudioLayout::Mix),
+            [
+                "-filter_complex",
+                "[1:a]highpass=f=80,volume=1.5,aresample=async=1000:first_pts=0[a1];\
+                 [2:a]aresample=async=1000:first_pts=0[a2];\
+                 [a1][a2]amix=inputs=2:duration=longest:normalize=0[aout3]",
+                "-map",
+                "0:v",
+                "-map",
+                "[aout3]",
+            ]
+        );
+    }
+
+    #[test]
+    fn separate_maps_each_source() {
+        assert_eq!(
+            mapped(AudioLayout::Separate),
+            [
+                "-filter_complex",
+                "[1:a]highpass=f=80,volume=1.5,aresample=async=1000:first_pts=0[a1];\
+                 [2:a]aresample=async=1000:first_pts=0[a2]",
+                "-map",
+                "0:v",
+                "-map",
+                "[a1]",
+                "-map",
+                "[a2]",
+            ]
+        );
+    }
+
+    #[test]
+    fn runtime_volume_names_every_source() {
+        let mut graph = FilterGraph::default();
+        let options = ChainOptions { runtime_volume: true, ..Default::default() };
+        let audio = compose(&sources()[1..], AudioLayout::Mix, 3, &options, &mut graph);
+        assert_eq!(audio, [Pad::Label("a1".to_string())]);
+        assert_eq!(graph.to_string(), "[3:a]volume@src0=volume=1,aresample=async=1000:first_pts=0[a1]");
+    }
+}
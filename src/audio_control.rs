@@ -0,0 +1,204 @@
+/// 运行时音频控制：不中断 FFmpeg，切换各音频源的静音或调整音量
+///
+/// 每个音频源的滤镜链中有一个命名的音量滤镜（`volume@src<序号>`），
+/// 通过 FFmpeg 的交互命令（向其 stdin 写入 `c` 加一行 `<目标> <时间> <命令> <参数>`）修改音量。
+/// 控制指令来自本程序的标准输入，Unix 上 SIGUSR1 还可切换麦克风静音，便于绑定快捷键实现按键通话。
+/// 原生捕获通过 FFmpeg 的 stdin 传送画面，无法使用此功能。
+use log::{info, warn};
+use std::io::{BufRead, Write};
+use std::process::{Child, ChildStdin};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::audio;
+use crate::config::RecorderConfig;
+use crate::level_meter::LevelMeter;
+
+/// 标准输入可用的控制指令
+const HELP: &str = "m [mic|system|all] 切换静音，mute / unmute [..] 静音 / 取消静音，v <mic|system|all> <倍数> 调整音量";
+
+/// 指令作用的音频源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Microphone,
+    System,
+    All,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Toggle,
+    Mute,
+    Unmute,
+    Volume(f64),
+}
+
+/// 解析一行控制指令，省略目标时作用于麦克风（没有麦克风时作用于全部音频源）
+fn parse_command(line: &str) -> Option<(Action, Option<Target>)> {
+    let mut parts = line.split_whitespace();
+    let verb = parts.next()?.to_ascii_lowercase();
+    let target = match parts.next().map(str::to_ascii_lowercase).as_deref() {
+        None => None,
+        Some("mic" | "microphone" | "麦克风") => Some(Target::Microphone),
+        Some("system" | "sys" | "系统声音") => Some(Target::System),
+        Some("all") => Some(Target::All),
+        Some(_) => return None,
+    };
+    let action = match verb.as_str() {
+        "m" | "t" | "toggle" => Action::Toggle,
+        "mute" => Action::Mute,
+        "u" | "unmute" => Action::Unmute,
+        "v" | "volume" => {
+            let volume: f64 = parts.next()?.parse().ok()?;
+            if !volume.is_finite() || !(0.0..=audio::MAX_VOLUME).contains(&volume) {
+                return None;
+            }
+            Action::Volume(volume)
+        }
+        _ => return None,
+    };
+    parts.next().is_none().then_some((action, target))
+}
+
+struct SourceState {
+    label: &'static str,
+    is_microphone: bool,
+    volume: f64,
+    muted: bool,
+}
+
+impl SourceState {
+    fn effective_volume(&self) -> f64 {
+        if self.muted {
+            0.0
+        } else {
+            self.volume
+        }
+    }
+}
+
+pub struct AudioControl {
+    /// FFmpeg 的标准输入，停止时需交还给子进程以发送 `q`
+    stdin: Mutex<Option<ChildStdin>>,
+    sources: Mutex<Vec<SourceState>>,
+    meter: Option<Arc<LevelMeter>>,
+}
+
+impl AudioControl {
+    /// 接管 FFmpeg 的标准输入并开始监听控制指令；未启用运行时控制时返回 None
+    pub fn start(config: &RecorderConfig, child: &mut Child, meter: Option<Arc<LevelMeter>>) -> Option<Arc<Self>> {
+        if !config.audio_control || !config.has_audio() {
+            return None;
+        }
+        let stdin = child.stdin.take()?;
+
+        let sources = config
+            .audio_sources
+            .iter()
+            .map(|s| SourceState {
+                label: s.label(),
+                is_microphone: s.is_microphone(),
+                volume: s.volume,
+                muted: false,
+            })
+            .collect();
+        let control = Arc::new(Self {
+            stdin: Mutex::new(Some(stdin)),
+            sources: Mutex::new(sources),
+            meter,
+        });
+
+        info!("🎛️  运行时音频控制已启用: {}", HELP);
+        let reader = control.clone();
+        thread::spawn(move || {
+            for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match parse_command(&line) {
+                    Some((action, target)) => reader.apply(action, target),
+                    None => warn!("无法识别的音频控制指令: {}（{}）", line.trim(), HELP),
+                }
+            }
+        });
+
+        #[cfg(unix)]
+        {
+            let toggler = control.clone();
+            tokio::spawn(async move {
+                use tokio::signal::unix::{signal, SignalKind};
+                let Ok(mut usr1) = signal(SignalKind::user_defined1()) else {
+                    warn!("无法监听 SIGUSR1，仅支持通过标准输入控制音频");
+                    return;
+                };
+                while usr1.recv().await.is_some() {
+                    toggler.apply(Action::Toggle, None);
+                }
+            });
+        }
+
+        Some(control)
+    }
+
+    /// 将标准输入交还给 FFmpeg 子进程（用于发送 `q` 正常结束）
+    pub fn release(&self, child: &mut Child) {
+        if let Some(stdin) = self.stdin.lock().expect("音频控制锁").take() {
+            child.stdin = Some(stdin);
+        }
+    }
+
+    fn apply(&self, action: Action, target: Option<Target>) {
+        let mut sources = self.sources.lock().expect("音频控制锁");
+        let target = target.unwrap_or(if sources.iter().any(|s| s.is_microphone) {
+            Target::Microphone
+        } else {
+            Target::All
+        });
+
+        let mut matched = false;
+        for (index, source) in sources.iter_mut().enumerate() {
+            let selected = match target {
+                Target::Microphone => source.is_microphone,
+                Target::System => !source.is_microphone,
+                Target::All => true,
+            };
+            if !selected {
+                continue;
+            }
+            matched = true;
+
+            match action {
+                Action::Toggle => source.muted = !source.muted,
+                Action::Mute => source.muted = true,
+                Action::Unmute => source.muted = false,
+                Action::Volume(volume) => source.volume = volume,
+            }
+            if let Err(e) = self.send_volume(index, source.effective_volume()) {
+                warn!("无法向 FFmpeg 发送音量命令: {}", e);
+                return;
+            }
+            if let Some(meter) = &self.meter {
+                meter.set_muted(index, source.effective_volume() == 0.0);
+            }
+            if source.muted {
+                info!("🔇 {}已静音", source.label);
+            } else {
+                info!("🔊 {}音量 {}", source.label, source.volume);
+            }
+        }
+        if !matched {
+            warn!("没有与该指令匹配的音频源");
+        }
+    }
+
+    /// 发送 FFmpeg 交互命令：`c` 后跟 `<滤镜实例> <时间> <命令> <参数>`，时间 -1 表示立即执行
+    fn send_volume(&self, index: usize, volume: f64) -> std::io::Result<()> {
+        let mut stdin = self.stdin.lock().expect("音频控制锁");
+        let Some(stdin) = stdin.as_mut() else {
+            return Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "FFmpeg 正在结束"));
+        };
+        let command = format!("c{} -1 volume {}\n", audio::volume_instance(index), volume);
+        stdin.write_all(command.as_bytes())?;
+        stdin.flush()
+    }
+}
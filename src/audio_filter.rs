@@ -0,0 +1,188 @@
+/// 麦克风实时处理链：高通、降噪（afftdn / arnndn）、压缩、增益
+///
+/// 按 高通 → 降噪 → 压缩 → 增益 的顺序串联，先去掉低频隆隆声再降噪，
+/// 压缩后再整体提升音量，避免增益把底噪一起放大。
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::error::RecorderError;
+use crate::ffmpeg_encoder::FfmpegCapabilities;
+use crate::validation::ValidationReport;
+
+/// 高通截止频率允许范围（Hz），人声基频最低约 80 Hz
+const HIGHPASS_RANGE: RangeInclusive<u32> = 20..=500;
+/// 增益允许的最大绝对值（dB）
+const MAX_GAIN_DB: f64 = 30.0;
+
+/// 适合语音的压缩参数：-18 dB 以上按 3:1 压缩，约 6 dB 补偿增益
+const COMPRESSOR: &str = "acompressor=threshold=-18dB:ratio=3:attack=20:release=250:makeup=2";
+/// FFT 降噪：降噪 12 dB，本底噪声 -40 dB，并持续跟踪噪声变化
+const FFT_DENOISE: &str = "afftdn=nr=12:nf=-40:tn=1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Denoise {
+    /// FFT 降噪（afftdn），无需模型，适合风扇、空调等稳定底噪
+    Fft,
+    /// RNNoise 神经网络降噪（arnndn），需要模型文件，对键盘声等突发噪声更有效
+    Rnn,
+}
+
+impl Denoise {
+    pub fn filter_name(&self) -> &'static str {
+        match self {
+            Denoise::Fft => "afftdn",
+            Denoise::Rnn => "arnndn",
+        }
+    }
+}
+
+impl fmt::Display for Denoise {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.filter_name())
+    }
+}
+
+impl FromStr for Denoise {
+    type Err = RecorderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "afftdn" | "fft" => Ok(Denoise::Fft),
+            "arnndn" | "rnn" | "rnnoise" => Ok(Denoise::Rnn),
+            _ => Err(RecorderError::UnsupportedDenoise(s.to_string())),
+        }
+    }
+}
+
+/// 麦克风处理设置，全部为空时不添加任何滤镜
+#[derive(Debug, Clone, Default)]
+pub struct AudioFilters {
+    /// 高通截止频率（Hz）
+    pub highpass: Option<u32>,
+    pub denoise: Option<Denoise>,
+    /// arnndn 使用的 RNNoise 模型文件（.rnnn）
+    pub rnnoise_model: Option<PathBuf>,
+    /// 动态范围压缩，拉近音量忽大忽小的说话声
+    pub compressor: bool,
+    /// 整体增益（dB）
+    pub gain_db: Option<f64>,
+}
+
+impl AudioFilters {
+    pub fn is_empty(&self) -> bool {
+        self.highpass.is_none() && self.denoise.is_none() && !self.compressor && self.gain_db.is_none()
+    }
+
+    /// 按顺序生成滤镜链，未启用任何处理时返回 None
+    pub fn chain(&self) -> Option<String> {
+        let mut filters = Vec::new();
+        if let Some(freq) = self.highpass {
+            filters.push(format!("highpass=f={}", freq));
+        }
+        match self.denoise {
+            Some(Denoise::Fft) => filters.push(FFT_DENOISE.to_string()),
+            Some(Denoise::Rnn) => {
+                if let Some(model) = &self.rnnoise_model {
+                    filters.push(format!("arnndn=m={}", escape_filter_path(&model.to_string_lossy())));
+                }
+            }
+            None => {}
+        }
+        if self.compressor {
+            filters.push(COMPRESSOR.to_string());
+        }
+        if let Some(gain) = self.gain_db {
+            filters.push(format!("volume={}dB", gain));
+        }
+        (!filters.is_empty()).then(|| filters.join(","))
+    }
+
+    /// `has_microphone` 为 false 时处理链不会生效，仅提示
+    pub fn validate(&self, ffmpeg: &FfmpegCapabilities, has_microphone: bool, report: &mut ValidationReport) {
+        if let Some(freq) = self.highpass {
+            if !HIGHPASS_RANGE.contains(&freq) {
+                report
+                    .error("highpass", freq, "高通截止频率超出允许范围")
+                    .suggest(format!(
+                        "取 {} - {} Hz，人声通常用 80 - 100",
+                        HIGHPASS_RANGE.start(),
+                        HIGHPASS_RANGE.end()
+                    ));
+            }
+        }
+
+        match (self.denoise, &self.rnnoise_model) {
+            (Some(Denoise::Rnn), None) => {
+                report
+                    .error("denoise", Denoise::Rnn, "arnndn 需要 RNNoise 模型文件")
+                    .suggest("通过 --rnnoise-model 指定 .rnnn 模型（如 rnnoise-models 中的 std.rnnn），或改用 afftdn");
+            }
+            (Some(Denoise::Rnn), Some(model)) => {
+                let shown = model.display();
+                if !model.is_file() {
+                    report.error("rnnoise_model", &shown, "模型文件不存在");
+                } else if model.to_string_lossy().contains('\'') {
+                    report
+                        .error("rnnoise_model", &shown, "路径中含有单引号，无法写入滤镜参数")
+                        .suggest("将模型移动到不含单引号的路径");
+                }
+            }
+            (_, Some(model)) => {
+                report
+                    .warning("rnnoise_model", model.display(), "未使用 arnndn 降噪，该设置不生效")
+                    .suggest("加上 --denoise arnndn");
+            }
+            (_, None) => {}
+        }
+
+        if let Some(gain) = self.gain_db {
+            if !gain.is_finite() || gain.abs() > MAX_GAIN_DB {
+                report
+                    .error("gain", gain, "增益超出允许范围")
+                    .suggest(format!("取 -{0} 到 {0} dB", MAX_GAIN_DB));
+            }
+        }
+
+        if self.is_empty() {
+            return;
+        }
+        if !has_microphone {
+            report
+                .warning("audio_filters", self.chain().unwrap_or_default(), "处理链只作用于麦克风，未启用麦克风时不生效")
+                .suggest("加上 --audio");
+            return;
+        }
+        for name in self.filter_names() {
+            if let Err(e) = ffmpeg.ensure_filter(name) {
+                report.reject("audio_filters", name, e);
+            }
+        }
+    }
+
+    /// 处理链用到的 FFmpeg 滤镜
+    fn filter_names(&self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        if self.highpass.is_some() {
+            names.push("highpass");
+        }
+        if let Some(denoise) = self.denoise {
+            names.push(denoise.filter_name());
+        }
+        if self.compressor {
+            names.push("acompressor");
+        }
+        if self.gain_db.is_some() {
+            names.push("volume");
+        }
+        names
+    }
+}
+
+/// 将文件路径写成滤镜参数值：统一为 `/` 分隔，转义 `:` 后整体加单引号
+///
+/// Windows 盘符中的冒号会被当作参数分隔符，例如 `C:\models\std.rnnn` 写为 `'C\:/models/std.rnnn'`。
+pub fn escape_filter_path(path: &str) -> String {
+    format!("'{}'", path.replace('\\', "/").replace(':', "\\:"))
+}
@@ -0,0 +1,96 @@
+/// 码率类型：解析 `5000k`、`5M`、`5000000` 等写法，支持按比例换算，
+/// 并根据分辨率与帧率给出推荐范围
+use std::fmt;
+use std::ops::{Div, Mul};
+use std::str::FromStr;
+
+use crate::error::RecorderError;
+
+/// 允许的码率下限（低于此值 FFmpeg 编码出的画面基本不可用）
+pub const MIN_BITRATE: Bitrate = Bitrate(64_000);
+/// 允许的码率上限
+pub const MAX_BITRATE: Bitrate = Bitrate(500_000_000);
+/// 默认音频码率
+pub const DEFAULT_AUDIO_BITRATE: Bitrate = Bitrate(128_000);
+
+/// 推荐范围：每像素每帧的比特数（H.264 基准，屏幕内容）
+const MIN_BITS_PER_PIXEL: f64 = 0.02;
+const MAX_BITS_PER_PIXEL: f64 = 0.4;
+
+/// 码率，单位 bit/s
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Bitrate(u64);
+
+impl Bitrate {
+    pub fn kbps(&self) -> f64 {
+        self.0 as f64 / 1000.0
+    }
+
+    /// 按比例缩放，结果至少为 1 kbit/s 并取整到 kbit
+    pub fn scale(&self, factor: f64) -> Bitrate {
+        let kbps = (self.kbps() * factor).round().max(1.0) as u64;
+        Bitrate(kbps * 1000)
+    }
+
+    /// 根据分辨率与帧率计算推荐码率范围
+    ///
+    /// `efficiency` 为相对 H.264 的码率系数，HEVC/VP9/AV1 约为 0.6-0.7。
+    pub fn recommended_range(width: u32, height: u32, fps: u32, efficiency: f64) -> (Bitrate, Bitrate) {
+        let pixels_per_second = width as f64 * height as f64 * fps as f64;
+        let low = Bitrate((pixels_per_second * MIN_BITS_PER_PIXEL * efficiency) as u64);
+        let high = Bitrate((pixels_per_second * MAX_BITS_PER_PIXEL * efficiency) as u64);
+        // 取整到 kbit，便于展示
+        (low.max(MIN_BITRATE).scale(1.0), high.min(MAX_BITRATE).scale(1.0))
+    }
+}
+
+impl fmt::Display for Bitrate {
+    /// 输出 FFmpeg 可直接使用的写法
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 >= 1_000_000 && self.0.is_multiple_of(1_000_000) {
+            write!(f, "{}M", self.0 / 1_000_000)
+        } else if self.0.is_multiple_of(1000) {
+            write!(f, "{}k", self.0 / 1000)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+impl FromStr for Bitrate {
+    type Err = RecorderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RecorderError::InvalidBitrate(s.to_string());
+
+        let trimmed = s.trim();
+        let (number, multiplier) = match trimmed.char_indices().last() {
+            Some((i, 'k' | 'K')) => (&trimmed[..i], 1_000.0),
+            Some((i, 'm' | 'M')) => (&trimmed[..i], 1_000_000.0),
+            Some(_) => (trimmed, 1.0),
+            None => return Err(invalid()),
+        };
+
+        let value: f64 = number.parse().map_err(|_| invalid())?;
+        if !value.is_finite() || value <= 0.0 {
+            return Err(invalid());
+        }
+        Ok(Bitrate((value * multiplier).round() as u64))
+    }
+}
+
+impl Mul<u64> for Bitrate {
+    type Output = Bitrate;
+
+    fn mul(self, rhs: u64) -> Bitrate {
+        Bitrate(self.0.saturating_mul(rhs))
+    }
+}
+
+impl Div<u64> for Bitrate {
+    type Output = Bitrate;
+
+    fn div(self, rhs: u64) -> Bitrate {
+        Bitrate(self.0 / rhs.max(1))
+    }
+}
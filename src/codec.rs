@@ -0,0 +1,205 @@
+/// 视频编码器注册表：统一用户输入的编码器别名、FFmpeg 编码器名称与编码格式
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::RecorderError;
+use crate::ffmpeg_encoder::FfmpegCapabilities;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoCodec {
+    /// H.264 软件编码 (libx264)
+    H264,
+    /// H.265/HEVC 软件编码 (libx265)
+    H265,
+    /// NVIDIA 硬件 H.264 编码
+    H264Nvenc,
+    /// NVIDIA 硬件 HEVC 编码
+    HevcNvenc,
+    /// Intel Quick Sync 硬件 H.264 编码
+    H264Qsv,
+    /// VP9 (libvpx-vp9)
+    Vp9,
+    /// AV1 (SVT-AV1)
+    Av1Svt,
+    /// AV1 (libaom)
+    Av1Aom,
+}
+
+/// 编码格式，用于容器兼容性判断
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodecFamily {
+    H264,
+    Hevc,
+    Vp9,
+    Av1,
+}
+
+impl VideoCodec {
+    pub const ALL: [VideoCodec; 8] = [
+        VideoCodec::H264,
+        VideoCodec::H265,
+        VideoCodec::H264Nvenc,
+        VideoCodec::HevcNvenc,
+        VideoCodec::H264Qsv,
+        VideoCodec::Vp9,
+        VideoCodec::Av1Svt,
+        VideoCodec::Av1Aom,
+    ];
+
+    /// 对应的 FFmpeg 编码器名称（-c:v 参数）
+    pub fn encoder(&self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "libx264",
+            VideoCodec::H265 => "libx265",
+            VideoCodec::H264Nvenc => "h264_nvenc",
+            VideoCodec::HevcNvenc => "hevc_nvenc",
+            VideoCodec::H264Qsv => "h264_qsv",
+            VideoCodec::Vp9 => "libvpx-vp9",
+            VideoCodec::Av1Svt => "libsvtav1",
+            VideoCodec::Av1Aom => "libaom-av1",
+        }
+    }
+
+    /// 命令行可接受的名称（首个为推荐写法）
+    fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            VideoCodec::H264 => &["h264", "libx264", "x264"],
+            VideoCodec::H265 => &["h265", "libx265", "x265", "hevc"],
+            VideoCodec::H264Nvenc => &["nvenc", "h264_nvenc"],
+            VideoCodec::HevcNvenc => &["hevc_nvenc", "h265_nvenc"],
+            VideoCodec::H264Qsv => &["qsv", "h264_qsv"],
+            VideoCodec::Vp9 => &["vp9", "libvpx-vp9"],
+            VideoCodec::Av1Svt => &["av1", "libsvtav1", "svtav1"],
+            VideoCodec::Av1Aom => &["libaom-av1", "aom-av1"],
+        }
+    }
+
+    pub fn family(&self) -> CodecFamily {
+        match self {
+            VideoCodec::H264 | VideoCodec::H264Nvenc | VideoCodec::H264Qsv => CodecFamily::H264,
+            VideoCodec::H265 | VideoCodec::HevcNvenc => CodecFamily::Hevc,
+            VideoCodec::Vp9 => CodecFamily::Vp9,
+            VideoCodec::Av1Svt | VideoCodec::Av1Aom => CodecFamily::Av1,
+        }
+    }
+
+    /// 达到相近画质时相对 H.264 的码率系数
+    pub fn efficiency(&self) -> f64 {
+        match self.family() {
+            CodecFamily::H264 => 1.0,
+            CodecFamily::Hevc | CodecFamily::Vp9 => 0.7,
+            CodecFamily::Av1 => 0.6,
+        }
+    }
+
+    /// 恒定质量模式下的默认质量值（与各编码器自身默认值一致）
+    pub fn default_quality(&self) -> u32 {
+        match self {
+            VideoCodec::H264 | VideoCodec::H264Nvenc | VideoCodec::H264Qsv => 23,
+            VideoCodec::H265 | VideoCodec::HevcNvenc => 28,
+            VideoCodec::Vp9 => 31,
+            VideoCodec::Av1Svt => 35,
+            VideoCodec::Av1Aom => 30,
+        }
+    }
+
+    /// 质量参数（CRF/QP）的上限
+    pub fn max_quality(&self) -> u32 {
+        match self {
+            VideoCodec::Vp9 | VideoCodec::Av1Svt | VideoCodec::Av1Aom => 63,
+            _ => 51,
+        }
+    }
+
+    /// 可用的速度预设（VP9/libaom 为 cpu-used 档位，SVT-AV1 为数字预设）
+    pub fn presets(&self) -> &'static [&'static str] {
+        match self {
+            VideoCodec::H264 | VideoCodec::H265 => &[
+                "ultrafast", "superfast", "veryfast", "faster", "fast", "medium", "slow", "slower",
+                "veryslow", "placebo",
+            ],
+            VideoCodec::H264Qsv => &["veryfast", "faster", "fast", "medium", "slow", "slower", "veryslow"],
+            VideoCodec::H264Nvenc | VideoCodec::HevcNvenc => &["p1", "p2", "p3", "p4", "p5", "p6", "p7"],
+            VideoCodec::Vp9 => &["0", "1", "2", "3", "4", "5", "6", "7", "8"],
+            VideoCodec::Av1Svt => &[
+                "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13",
+            ],
+            VideoCodec::Av1Aom => &["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"],
+        }
+    }
+
+    /// B 帧数量上限，VP9/AV1 没有 B 帧概念
+    pub fn max_bframes(&self) -> u32 {
+        match self {
+            VideoCodec::H264 | VideoCodec::H265 | VideoCodec::H264Qsv => 16,
+            VideoCodec::H264Nvenc | VideoCodec::HevcNvenc => 4,
+            VideoCodec::Vp9 | VideoCodec::Av1Svt | VideoCodec::Av1Aom => 0,
+        }
+    }
+
+    /// 编码速度相关参数（各编码器的预设体系不同）
+    ///
+    /// 未指定 `preset` 时，`realtime` 为 true 用于推流，优先编码速度；否则用于本地录制，兼顾画质。
+    pub fn speed_args<'a>(&self, preset: Option<&'a str>, realtime: bool) -> Vec<&'a str> {
+        match self {
+            VideoCodec::H264 | VideoCodec::H265 | VideoCodec::H264Qsv => {
+                vec!["-preset", preset.unwrap_or(if realtime { "veryfast" } else { "medium" })]
+            }
+            VideoCodec::H264Nvenc | VideoCodec::HevcNvenc => {
+                vec!["-preset", preset.unwrap_or(if realtime { "p2" } else { "p4" })]
+            }
+            VideoCodec::Vp9 => vec![
+                "-deadline", if realtime { "realtime" } else { "good" },
+                "-cpu-used", preset.unwrap_or(if realtime { "8" } else { "4" }),
+                "-row-mt", "1",
+            ],
+            VideoCodec::Av1Svt => vec!["-preset", preset.unwrap_or(if realtime { "10" } else { "8" })],
+            VideoCodec::Av1Aom => {
+                let mut args = vec![
+                    "-cpu-used", preset.unwrap_or(if realtime { "8" } else { "4" }),
+                    "-row-mt", "1",
+                ];
+                if realtime {
+                    args.extend(["-usage", "realtime"]);
+                }
+                args
+            }
+        }
+    }
+
+    /// 检查已安装的 FFmpeg 是否提供该编码器，不提供时给出可用的替代项
+    pub fn ensure_available(&self, ffmpeg: &FfmpegCapabilities) -> Result<(), RecorderError> {
+        if ffmpeg.has_encoder(self.encoder()) {
+            return Ok(());
+        }
+
+        let alternatives: Vec<String> = VideoCodec::ALL
+            .iter()
+            .filter(|c| **c != *self && ffmpeg.has_encoder(c.encoder()))
+            .map(|c| format!("{} ({})", c, c.encoder()))
+            .collect();
+
+        Err(RecorderError::EncoderUnavailable {
+            encoder: self.encoder().to_string(),
+            alternatives,
+        })
+    }
+}
+
+impl fmt::Display for VideoCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.aliases()[0])
+    }
+}
+
+impl FromStr for VideoCodec {
+    type Err = RecorderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        VideoCodec::ALL
+            .into_iter()
+            .find(|c| c.aliases().contains(&lower.as_str()))
+            .ok_or_else(|| RecorderError::UnsupportedCodec(s.to_string()))
+    }
+}
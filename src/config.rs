@@ -1,29 +1,98 @@
+use crate::audio::{AudioCodec, AudioLayout, AudioSettings, AudioSource, AudioSourceKind, MAX_AV_OFFSET_SECONDS, MAX_VOLUME};
+use crate::audio_filter::AudioFilters;
+use crate::bitrate::{Bitrate, MAX_BITRATE, MIN_BITRATE};
+use crate::codec::VideoCodec;
+use crate::constraints;
+use crate::container::Container;
 use crate::error::RecorderError;
+use crate::ffmpeg_encoder::{self, FfmpegCapabilities};
+use crate::level_meter::{self, SilenceSettings};
+use crate::{loudness, native_capture};
+use crate::overlay::TextOverlay;
+use crate::orientation::Orientation;
+use crate::pip::PictureInPicture;
+use crate::privacy_mask::{self, PrivacyMask};
+use crate::rate_control::RateControl;
+use crate::tuning::{EncoderTuning, Tune, LOW_LATENCY_GOP_SECONDS, STREAM_GOP_SECONDS};
+use crate::validation::ValidationReport;
+use crate::watermark::Watermark;
 use anyhow::Result;
+use std::path::Path;
+use std::sync::Arc;
 use url::Url;
 
 #[derive(Clone, Debug)]
 pub struct RecorderConfig {
     pub output: String,
     pub device: String,
-    /// 是否启用音频采集
-    pub audio_enabled: bool,
-    /// 可选音频设备名称（跨平台，Windows 示例: "Microphone"）
-    pub audio_device: Option<String>,
+    /// 音频源（麦克风、系统声音），为空表示不采集音频
+    pub audio_sources: Vec<AudioSource>,
+    /// 多个音频源时混合为一条音轨还是分轨输出
+    pub audio_layout: AudioLayout,
+    /// 音频编码设置（编码器、码率、采样率、声道）
+    pub audio: AudioSettings,
+    /// 麦克风实时处理链（高通、降噪、压缩、增益）
+    pub audio_filters: AudioFilters,
+    /// 录制结束后响度标准化的目标（LUFS），None 表示不处理
+    pub loudness_target: Option<f64>,
+    /// 运行时音频控制（标准输入指令 / SIGUSR1 切换静音、调整音量）
+    pub audio_control: bool,
+    /// 实时电平监测（FFmpeg 提供所需滤镜时生效）
+    pub level_meter: bool,
+    /// 静音告警设置
+    pub silence: SilenceSettings,
     /// 是否在采集时绘制鼠标指针（仅对 gdigrab 生效）
     pub draw_mouse: bool,
     pub fps: u32,
     pub resolution: String,
-    pub bitrate: String,
-    pub codec: String,
+    pub bitrate: Bitrate,
+    pub codec: VideoCodec,
+    /// 码率控制模式（CRF/CQP/VBR/CBR）
+    pub rate_control: RateControl,
+    /// 编码器调优参数（预设、tune、GOP、profile/level、B 帧、线程）
+    pub tuning: EncoderTuning,
     pub duration: u32,
     #[allow(dead_code)]
     pub is_stream: bool,
 
     /// 流媒体协议类型
     pub protocol: StreamProtocol,
+
+    /// 崩溃安全录制：先写入分片 MP4，正常结束后再重封装为普通 MP4
+    pub crash_safe: bool,
+
+    /// 显式指定的输出容器（--format），未指定时根据扩展名推断
+    pub format: Option<Container>,
+
+    /// 低延迟模式：零延迟调优、无 B 帧、短 GOP、小缓冲与低延迟封装参数
+    pub low_latency: bool,
+    /// 在画面上叠加毫秒级本地时间，用于测量端到端延迟
+    pub latency_probe: bool,
+    /// 画面文字叠加（时间、主机名、自定义文字、帧序号）
+    pub overlay: TextOverlay,
+    /// 隐私遮挡区域（坐标相对采集区域）
+    pub masks: Vec<PrivacyMask>,
+    /// 画中画（摄像头或其他视频源），None 表示不叠加
+    pub pip: Option<PictureInPicture>,
+    /// 图片水印，None 表示不叠加
+    pub watermark: Option<Watermark>,
+    /// 输出方向与画幅（裁出区域、旋转/翻转、竖屏等预设）
+    pub orientation: Orientation,
+
+    /// 纯音频模式：不采集屏幕，只录制或推流音频源
+    pub audio_only: bool,
+
+    /// 使用原生屏幕捕获：程序内采集画面，经 stdin 以原始帧送入 FFmpeg
+    pub native_capture: bool,
+
+    /// 音频相对视频的偏移（秒），正值使音频延后，负值使音频提前
+    pub av_offset: f64,
+
+    /// 已探测的 FFmpeg 位置与能力，所有 FFmpeg 命令均通过它构建
+    pub ffmpeg: Arc<FfmpegCapabilities>,
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum StreamProtocol {
     RTMP,
@@ -32,62 +101,493 @@ pub enum StreamProtocol {
 }
 
 impl RecorderConfig {
-    pub fn validate(&self) -> Result<()> {
+    /// 检查全部配置项，问题记入报告而不是在第一个错误处返回
+    ///
+    /// 纯音频模式不采集屏幕，跳过分辨率、帧率与视频编码相关的检查。
+    pub fn validate(&self, report: &mut ValidationReport) {
+        if self.audio_only {
+            if !self.has_audio() {
+                report
+                    .error("audio_only", true, "纯音频模式没有可采集的音频源")
+                    .suggest("加上 --audio 和/或 --system-audio");
+            }
+            if !self.overlay.is_empty() {
+                report.warning("overlay", true, "纯音频模式没有画面，文字叠加不生效");
+            }
+            if let Some(watermark) = &self.watermark {
+                report.warning("watermark", watermark.path.display(), "纯音频模式没有画面，水印不生效");
+            }
+            if !self.masks.is_empty() {
+                report.warning("mask", self.masks.len(), "纯音频模式没有画面，遮挡区域不生效");
+            }
+            if let Some(pip) = &self.pip {
+                report.warning("pip", &pip.input, "纯音频模式没有画面，画中画不生效");
+            }
+            if !self.orientation.is_empty() {
+                report.warning("orientation", true, "纯音频模式没有画面，输出方向设置不生效");
+            }
+        } else {
+            self.validate_video(report);
+        }
+
+        // 验证输出
+        if self.output.is_empty() {
+            report.reject("output", "", RecorderError::EmptyOutput);
+        } else {
+            // 根据协议类型验证
+            match self.protocol {
+                StreamProtocol::RTMP => {
+                    self.validate_stream_url("rtmp", report);
+                    self.validate_rtmp_codec(report);
+                }
+                StreamProtocol::RTSP => self.validate_stream_url("rtsp", report),
+                StreamProtocol::File => self.validate_container(report),
+            }
+        }
+
+        // 验证音频设备与编码设置（如果启用）
+        self.validate_audio(report);
+        self.validate_av_offset(report);
+        let has_microphone = self.audio_sources.iter().any(AudioSource::is_microphone);
+        self.audio_filters.validate(&self.ffmpeg, has_microphone, report);
+        self.validate_loudness(report);
+        self.validate_level_meter(report);
+        if self.audio_control && !self.has_audio() {
+            report
+                .warning("audio_control", true, "未启用音频采集，该设置不生效")
+                .suggest("加上 --audio 或 --system-audio");
+        }
+
+        if self.audio_control && self.native_capture && self.has_audio() {
+            report
+                .warning("audio_control", true, "原生捕获通过 FFmpeg 的标准输入传送画面，运行时音频控制不可用")
+                .suggest("加 --use-gdigrab 改用 FFmpeg 设备采集");
+        }
+
+        self.validate_capabilities(report);
+    }
+
+    /// 屏幕采集与视频编码相关的检查
+    fn validate_video(&self, report: &mut ValidationReport) {
         // 验证分辨率格式
-        self.get_resolution_parts()?;
+        if self.get_resolution_parts().is_err() {
+            report.reject(
+                "resolution",
+                &self.resolution,
+                RecorderError::InvalidResolution(self.resolution.clone()),
+            );
+        }
 
         // 验证 FPS
         if self.fps < 1 || self.fps > 120 {
-            return Err(RecorderError::InvalidFps(self.fps).into());
+            report.reject("fps", self.fps, RecorderError::InvalidFps(self.fps));
         }
 
-        // 验证编码器
-        let valid_codecs = ["h264", "h265", "libx264", "libx265", "nvenc", "qsv"];
-        if !valid_codecs.contains(&self.codec.as_str()) {
-            return Err(RecorderError::UnsupportedCodec(self.codec.clone()).into());
+        // 遮挡、裁出区域按实际采集的画面检查（原生捕获为整个显示器），画中画、码率等按编码输出的尺寸检查
+        let capture_size = self.capture_size();
+        let output_size = capture_size.map(|c| self.orientation.output_size(c));
+
+        // 验证码率
+        self.validate_bitrate(output_size, report);
+
+        // 验证码率控制与编码器调优参数
+        self.rate_control.validate(self.codec, self.bitrate, report);
+        self.tuning.validate(self.codec, report);
+
+        if self.protocol != StreamProtocol::File && self.rate_control.is_constant_quality() {
+            report
+                .warning("rate_control", &self.rate_control, "推流时码率波动较大")
+                .suggest("改用 cbr");
         }
 
-        // 验证输出
-        if self.output.is_empty() {
-            return Err(RecorderError::EmptyOutput.into());
+        if self.latency_probe && !self.ffmpeg.has_build_flag("libfreetype") {
+            report
+                .warning("latency_probe", true, "当前 FFmpeg 可能未启用 libfreetype，时间戳叠加（drawtext）可能无法使用")
+                .suggest("使用带 --enable-libfreetype 编译的 FFmpeg");
+        }
+        // drawtext 的 %3N（毫秒）自 FFmpeg 6.1 起可用，旧版本会原样显示 %3N
+        if self.latency_probe && !self.ffmpeg.version_at_least(6, 1) {
+            report
+                .warning("latency_probe", &self.ffmpeg.version, "FFmpeg 6.1 之前的 drawtext 不支持毫秒（%3N），时间戳无法用于测量延迟")
+                .suggest("升级到 FFmpeg 6.1 或更新版本");
+        }
+        privacy_mask::validate(&self.masks, capture_size, &self.ffmpeg, report);
+        self.orientation.validate(&self.ffmpeg, capture_size, report);
+        self.overlay.validate(&self.ffmpeg, self.latency_probe, report);
+        if let Some(pip) = &self.pip {
+            pip.validate(&self.ffmpeg, output_size, report);
         }
+        if let Some(watermark) = &self.watermark {
+            watermark.validate(&self.ffmpeg, report);
+        }
+    }
 
-        // 根据协议类型验证
-        match self.protocol {
-            StreamProtocol::RTMP => self.validate_rtmp_url()?,
-            StreamProtocol::RTSP => self.validate_rtsp_url()?,
-            StreamProtocol::File => {},
+    /// 低延迟模式：为未显式设置的编码参数填入低延迟默认值
+    pub fn apply_low_latency(&mut self) {
+        if !self.low_latency {
+            return;
+        }
+
+        if self.tuning.tune.is_none() {
+            if Tune::ZeroLatency.supported_by(self.codec) {
+                self.tuning.tune = Some(Tune::ZeroLatency);
+            } else {
+                log::warn!("{} 不支持 zerolatency 调优，低延迟效果有限", self.codec);
+            }
+        }
+        if self.codec.max_bframes() > 0 {
+            self.tuning.bframes.get_or_insert(0);
+        }
+        self.tuning.gop_seconds.get_or_insert(LOW_LATENCY_GOP_SECONDS);
+
+        // VBV 缓冲缩小到半秒码率，减少编码端排队
+        self.rate_control.set_default_bufsize(self.bitrate / 2);
+    }
+
+    /// 低延迟模式下的输入参数（需位于对应 -i 之前），尽量减少输入端缓冲
+    pub fn low_latency_input_args(&self) -> Vec<&'static str> {
+        if !self.low_latency {
+            return Vec::new();
+        }
+        vec!["-fflags", "nobuffer", "-thread_queue_size", "2"]
+    }
+
+    /// 低延迟模式下的推流输出参数：逐包刷新、不做封装层预缓冲
+    ///
+    /// 文件输出没有观看端延迟，逐包刷新只会增加写入次数，不添加。
+    pub fn low_latency_output_args(&self) -> Vec<&'static str> {
+        if !self.low_latency || self.protocol == StreamProtocol::File {
+            return Vec::new();
+        }
+        vec!["-flush_packets", "1", "-muxdelay", "0", "-muxpreload", "0"]
+    }
+
+    /// 延迟测量：在左上角叠加毫秒级本地时间（需要 FFmpeg 6.1+ 的 `%N` 支持）
+    ///
+    /// 用播放器打开本地服务器上的流，与本机时钟对比即可得到端到端延迟。
+    pub fn latency_probe_filter(&self) -> Option<&'static str> {
+        self.latency_probe.then_some(
+            "drawtext=text='%{localtime\\:%T.%3N}':x=16:y=16:fontsize=48:fontcolor=white:box=1:boxcolor=black@0.6:boxborderw=8",
+        )
+    }
+
+    /// 延迟测量时间戳与文字叠加，接在缩放与像素格式转换之后
+    pub fn overlay_filters(&self) -> Vec<String> {
+        self.latency_probe_filter()
+            .map(str::to_string)
+            .into_iter()
+            .chain(self.overlay.filter_chain())
+            .collect()
+    }
+
+    /// 根据已安装 FFmpeg 的能力检查编码器、封装器与输入设备
+    fn validate_capabilities(&self, report: &mut ValidationReport) {
+        if !self.audio_only {
+            if let Err(e) = self.codec.ensure_available(&self.ffmpeg) {
+                report.reject("codec", self.codec, e);
+            }
+
+            // 屏幕输入设备缺失时仍可使用原生捕获，仅提示
+            let input_format = self.get_input_format();
+            if !self.ffmpeg.has_input_device(input_format) {
+                report.warning("device", input_format, "当前 FFmpeg 不支持该输入设备，仅能使用原生屏幕捕获");
+            }
+        }
+
+        // 无法识别的扩展名由 FFmpeg 自行推断封装格式，无从检查
+        let muxer = match self.protocol {
+            StreamProtocol::RTMP => Some("flv"),
+            StreamProtocol::RTSP => Some("rtsp"),
+            StreamProtocol::File => self.container().map(|c| c.muxer()),
+        };
+        if let Some(muxer) = muxer.filter(|m| !self.ffmpeg.has_muxer(m)) {
+            report
+                .error("format", muxer, "当前 FFmpeg 不支持该封装")
+                .suggest("更换输出格式，或使用完整构建的 FFmpeg");
+        }
+
+        if self.has_audio() {
+            let audio_format = self.get_audio_input_format();
+            if !self.ffmpeg.has_input_device(audio_format) {
+                report
+                    .error("audio", audio_format, "当前 FFmpeg 不支持该音频输入设备")
+                    .suggest("去掉 --audio，或使用包含该输入设备的 FFmpeg");
+            }
+        }
+    }
+
+    /// 音画偏移需要同时存在音频与视频
+    fn validate_av_offset(&self, report: &mut ValidationReport) {
+        if self.av_offset == 0.0 {
+            return;
+        }
+        if !self.av_offset.is_finite() || self.av_offset.abs() > MAX_AV_OFFSET_SECONDS {
+            report
+                .error("av_offset", self.av_offset, "音画偏移超出允许范围")
+                .suggest(format!(
+                    "使用 -{0} 到 {0} 之间的秒数",
+                    MAX_AV_OFFSET_SECONDS
+                ));
+        } else if !self.has_audio() {
+            report
+                .warning("av_offset", self.av_offset, "未启用音频采集，该设置不生效")
+                .suggest("加上 --audio 或 --system-audio");
+        } else if self.audio_only {
+            report.warning("av_offset", self.av_offset, "纯音频模式没有视频可对齐，该设置不生效");
         }
+    }
+
+    /// 静音告警依赖电平监测
+    fn validate_level_meter(&self, report: &mut ValidationReport) {
+        let silence = &self.silence;
+        if !level_meter::SILENCE_THRESHOLD_RANGE.contains(&silence.threshold_db) {
+            report
+                .error("silence_threshold", silence.threshold_db, "静音阈值超出允许范围")
+                .suggest(format!(
+                    "取 {} 到 {} dB，常用 -60",
+                    level_meter::SILENCE_THRESHOLD_RANGE.start(),
+                    level_meter::SILENCE_THRESHOLD_RANGE.end()
+                ));
+        }
+        if !silence.abort {
+            return;
+        }
+        if silence.timeout_secs == 0 {
+            report
+                .error("abort_on_silence", true, "静音告警已关闭（--silence-timeout 0），无法据此终止")
+                .suggest("设置 --silence-timeout 为大于 0 的秒数");
+        } else if !self.has_audio() {
+            report
+                .warning("abort_on_silence", true, "未启用音频采集，该设置不生效")
+                .suggest("加上 --audio 或 --system-audio");
+        } else if !self.level_meter {
+            report
+                .error("abort_on_silence", true, "电平监测已关闭，无法检测静音")
+                .suggest("去掉 --no-level-meter");
+        } else if !self.level_metering() {
+            report
+                .error("abort_on_silence", true, "当前 FFmpeg 缺少电平监测所需的滤镜")
+                .suggest(format!("使用提供 {} 滤镜的 FFmpeg", level_meter::REQUIRED_FILTERS.join("、")));
+        }
+    }
 
-        // 验证音频设备（如果启用）
-        if self.audio_enabled {
-            if let Some(dev) = &self.audio_device {
-                if dev.trim().is_empty() {
-                    return Err(RecorderError::SystemError("空的音频设备名称".to_string()).into());
+    /// 响度标准化在录制结束后处理输出文件，推流无法使用
+    fn validate_loudness(&self, report: &mut ValidationReport) {
+        let Some(target) = self.loudness_target else {
+            return;
+        };
+        if !loudness::TARGET_RANGE.contains(&target) {
+            report
+                .error("loudness_target", target, "目标响度超出允许范围")
+                .suggest(format!(
+                    "取 {} 到 {} LUFS，EBU R128 为 -23，播客/网络视频常用 -16",
+                    loudness::TARGET_RANGE.start(),
+                    loudness::TARGET_RANGE.end()
+                ));
+        }
+        if self.protocol != StreamProtocol::File {
+            report
+                .error("loudnorm", true, "响度标准化只能处理录制文件，不能用于推流")
+                .suggest("去掉 --loudnorm，或改为录制到文件");
+        } else if !self.has_audio() {
+            report
+                .warning("loudnorm", true, "未启用音频采集，该设置不生效")
+                .suggest("加上 --audio 或 --system-audio");
+        } else if let Err(e) = self.ffmpeg.ensure_filter("loudnorm") {
+            report.reject("loudnorm", true, e);
+        }
+    }
+
+    fn validate_audio(&self, report: &mut ValidationReport) {
+        if !self.has_audio() {
+            if let Some(codec) = self.audio.codec {
+                report
+                    .warning("audio_codec", codec, "未启用音频采集，该设置不生效")
+                    .suggest("加上 --audio 或 --system-audio");
+            }
+            return;
+        }
+
+        // 输入设备类型不可用时已在 validate_capabilities 中报告，不再逐个探测
+        let can_probe = self.ffmpeg.has_input_device(self.get_audio_input_format());
+        for source in &self.audio_sources {
+            let named = match &source.kind {
+                AudioSourceKind::Microphone(Some(dev)) if dev.trim().is_empty() => {
+                    report
+                        .error("audio_device", dev, "空的音频设备名称")
+                        .suggest("去掉 --audio-device 以使用默认设备");
+                    false
+                }
+                // Windows/macOS 没有通用的回环采集设备
+                AudioSourceKind::System if !cfg!(target_os = "linux") => {
+                    report
+                        .error("system_audio", source.device(), "系统声音采集仅支持 Linux (PulseAudio)")
+                        .suggest("Windows 可用 --audio-device 指定“立体声混音”，macOS 需安装 BlackHole 等虚拟声卡");
+                    false
                 }
+                _ => true,
+            };
+            source.validate(report);
+            // 启动前确认设备存在，避免 FFmpeg 启动后才因找不到设备而退出
+            if named && can_probe && !ffmpeg_encoder::probe_audio_device(&self.ffmpeg, &source.device()) {
+                report
+                    .error("audio_device", source.device(), format!("未找到{}设备", source.label()))
+                    .suggest("运行 `screen_recorder devices audio` 查看可用设备");
             }
+            if !(0.0..=MAX_VOLUME).contains(&source.volume) {
+                report
+                    .error("volume", source.volume, format!("{}音量超出范围", source.label()))
+                    .suggest(format!("取 0-{} 之间的倍数，1 为原始音量", MAX_VOLUME));
+            }
+        }
+
+        // FLV 只能容纳一条音轨
+        if self.audio_layout == AudioLayout::Separate
+            && self.audio_sources.len() > 1
+            && (self.protocol == StreamProtocol::RTMP || self.container() == Some(Container::Flv))
+        {
+            report
+                .error("separate_audio_tracks", true, "FLV/RTMP 只支持一条音轨")
+                .suggest("去掉 --separate-audio-tracks 以混音输出，或录制为 mkv/mp4");
+        }
+
+        let codec = self.audio_codec();
+        self.audio.validate(codec, report);
+
+        if !self.output_supports_audio(codec) {
+            // 文件输出显示容器名，推流显示协议名
+            let target = self.container().map(|c| c.to_string()).unwrap_or_else(|| format!("{:?}", self.protocol));
+            let options: Vec<String> = AudioCodec::ALL
+                .iter()
+                .filter(|c| self.output_supports_audio(**c))
+                .map(|c| c.to_string())
+                .collect();
+            report
+                .error("audio_codec", codec, format!("{} 不能封装该音频编码", target))
+                .suggest(format!("可选 {}", options.join(", ")));
+        }
+
+        if !self.ffmpeg.has_encoder(codec.encoder()) {
+            let alternatives: Vec<String> = AudioCodec::ALL
+                .iter()
+                .filter(|c| self.output_supports_audio(**c) && self.ffmpeg.has_encoder(c.encoder()))
+                .map(|c| c.to_string())
+                .collect();
+            report.reject(
+                "audio_codec",
+                codec,
+                RecorderError::EncoderUnavailable { encoder: codec.encoder().to_string(), alternatives },
+            );
+        }
+    }
+
+    /// 输出能否承载该音频编码：RTMP 使用 FLV 封装，RTSP 经 RTP 传输不支持 FLAC 与小端 PCM
+    fn output_supports_audio(&self, codec: AudioCodec) -> bool {
+        match self.protocol {
+            StreamProtocol::RTMP => Container::Flv.supports_audio_codec(codec),
+            StreamProtocol::RTSP => matches!(codec, AudioCodec::Aac | AudioCodec::Opus | AudioCodec::Mp3),
+            // 未知容器已在 validate_container 中报告
+            StreamProtocol::File => self.container().is_none_or(|c| c.supports_audio_codec(codec)),
+        }
+    }
+
+    /// 码率必须在硬性范围内；超出分辨率/帧率的推荐范围时仅提示
+    fn validate_bitrate(&self, resolution: Option<(u32, u32)>, report: &mut ValidationReport) {
+        if self.bitrate < MIN_BITRATE || self.bitrate > MAX_BITRATE {
+            report.reject(
+                "bitrate",
+                self.bitrate,
+                RecorderError::BitrateOutOfRange {
+                    bitrate: self.bitrate.to_string(),
+                    min: MIN_BITRATE.to_string(),
+                    max: MAX_BITRATE.to_string(),
+                },
+            );
+            return;
+        }
+
+        // 恒定质量模式不以码率为目标，无需比较
+        if self.rate_control.is_constant_quality() {
+            return;
         }
 
-        Ok(())
+        let Some((w, h)) = resolution else {
+            return;
+        };
+        let (low, high) = Bitrate::recommended_range(w, h, self.fps, self.codec.efficiency());
+        // 明显偏离的情况已由编码约束检查报告
+        if constraints::is_far_outside(self.bitrate, low, high) {
+            return;
+        }
+        if self.bitrate < low || self.bitrate > high {
+            report
+                .warning("bitrate", self.bitrate, format!("超出 {}x{}@{}fps 的推荐范围", w, h, self.fps))
+                .suggest(format!("取 {} - {} 之间的值", low, high));
+        }
     }
 
-    fn validate_rtmp_url(&self) -> Result<()> {
-        if !self.output.starts_with("rtmp://") && !self.output.starts_with("rtmps://") {
-            return Err(RecorderError::InvalidStreamUrl(self.output.clone()).into());
+    fn validate_container(&self, report: &mut ValidationReport) {
+        let Some(container) = self.container() else {
+            // 未列出的扩展名（如 .avi）仍交给 FFmpeg 按扩展名推断，只是无法预先检查编码器兼容性
+            report
+                .warning("output", &self.output, "无法识别扩展名对应的容器，将由 FFmpeg 推断封装格式，不检查编码器兼容性")
+                .suggest(RecorderError::UnsupportedContainer(self.output.clone()).suggestion().unwrap_or_default());
+            return;
+        };
+        if self.format.is_none() && Path::new(&self.output).extension().is_none() {
+            report
+                .warning("output", &self.output, format!("输出没有扩展名，按 {} 封装", container))
+                .suggest(format!("将输出文件扩展名改为 .{}", container));
         }
-        match Url::parse(&self.output) {
-            Ok(_) => Ok(()),
-            Err(_) => Err(RecorderError::InvalidStreamUrl(self.output.clone()).into()),
+
+        if container.is_audio_only() && !self.audio_only {
+            report
+                .error("output", &self.output, format!("{} 为纯音频格式，不能容纳视频", container))
+                .suggest("加上 --audio-only 只录制音频，或改用 mp4/mkv");
+        } else if !self.audio_only && !container.supports_video_codec(self.codec) {
+            report.reject(
+                "codec",
+                self.codec,
+                RecorderError::IncompatibleCodec {
+                    codec: self.codec.to_string(),
+                    container: container.to_string(),
+                },
+            );
+        }
+
+        if let (Some(explicit), Some(inferred)) = (self.format, Container::from_path(&self.output)) {
+            if explicit != inferred {
+                report
+                    .warning("format", explicit, format!("与输出扩展名不一致（{}），将按 {} 封装", inferred, explicit))
+                    .suggest(format!("将输出文件扩展名改为 .{}", explicit));
+            }
         }
     }
 
-    fn validate_rtsp_url(&self) -> Result<()> {
-        if !self.output.starts_with("rtsp://") && !self.output.starts_with("rtsps://") {
-            return Err(RecorderError::InvalidStreamUrl(self.output.clone()).into());
+    /// RTMP 使用 FLV 封装，视频编码需能放入 FLV
+    fn validate_rtmp_codec(&self, report: &mut ValidationReport) {
+        if !self.audio_only && !Container::Flv.supports_video_codec(self.codec) {
+            report
+                .error("codec", self.codec, "RTMP 使用 FLV 封装，只能推送 H.264 视频")
+                .suggest("改用 h264、nvenc 或 qsv，其他编码请推流到 RTSP 或录制为文件");
         }
-        match Url::parse(&self.output) {
-            Ok(_) => Ok(()),
-            Err(_) => Err(RecorderError::InvalidStreamUrl(self.output.clone()).into()),
+    }
+
+    /// `scheme` 为 rtmp 或 rtsp，同时接受对应的加密协议（rtmps / rtsps）
+    fn validate_stream_url(&self, scheme: &'static str, report: &mut ValidationReport) {
+        let secure = format!("{}s://", scheme);
+        if !self.output.starts_with(&format!("{}://", scheme)) && !self.output.starts_with(&secure) {
+            report.reject(
+                "output",
+                &self.output,
+                RecorderError::InvalidStreamUrl { url: self.output.clone(), scheme },
+            );
+            return;
+        }
+        if let Err(e) = Url::parse(&self.output) {
+            report
+                .error("output", &self.output, format!("无法解析 {} 流地址: {}", scheme, e))
+                .suggest("检查主机名、端口与路径是否完整");
         }
     }
 
@@ -101,14 +601,84 @@ impl RecorderConfig {
         }
     }
 
+    /// 视频编码参数（码率控制 + 调优），`realtime` 为 true 时默认使用更快的预设
+    pub fn video_encoder_args(&self, realtime: bool) -> Vec<String> {
+        // 推流默认固定关键帧间隔，满足 RTMP/RTSP 接收端要求
+        let default_gop = (self.protocol != StreamProtocol::File).then_some(STREAM_GOP_SECONDS);
+
+        let mut args = self.rate_control.encoder_args(self.codec, self.bitrate);
+        args.extend(self.tuning.encoder_args(self.codec, self.fps, realtime, default_gop));
+        args
+    }
+
+    /// 实际使用的音频编码器：优先 --audio-codec，其次按容器/协议选择
+    pub fn audio_codec(&self) -> AudioCodec {
+        self.audio.codec.unwrap_or_else(|| match self.protocol {
+            StreamProtocol::File => self.container().unwrap_or(Container::Mp4).default_audio_codec(),
+            StreamProtocol::RTMP | StreamProtocol::RTSP => AudioCodec::Aac,
+        })
+    }
+
+    pub fn has_audio(&self) -> bool {
+        !self.audio_sources.is_empty()
+    }
+
+    /// 是否对音频源做实时电平监测：需要启用音频，且 FFmpeg 提供所需滤镜
+    pub fn level_metering(&self) -> bool {
+        self.level_meter
+            && self.has_audio()
+            && level_meter::REQUIRED_FILTERS.iter().all(|f| self.ffmpeg.has_filter(f))
+    }
+
+    /// 输出中的音轨数量：混音为一条，分轨为每个音频源一条
+    pub fn audio_track_count(&self) -> usize {
+        match self.audio_layout {
+            AudioLayout::Mix => self.audio_sources.len().min(1),
+            AudioLayout::Separate => self.audio_sources.len(),
+        }
+    }
+
+    /// 音频编码参数，未启用音频时为空
+    pub fn audio_encoder_args(&self) -> Vec<String> {
+        if !self.has_audio() {
+            return Vec::new();
+        }
+        self.audio.encoder_args(self.audio_codec())
+    }
+
+    /// 文件输出使用的容器：优先 --format，其次根据扩展名推断；推流输出返回 None
+    pub fn container(&self) -> Option<Container> {
+        if self.protocol != StreamProtocol::File {
+            return None;
+        }
+        // 没有扩展名时 FFmpeg 无法推断格式，按 MP4 封装；未列出的扩展名返回 None，交给 FFmpeg 推断
+        self.format
+            .or_else(|| Container::from_path(&self.output))
+            .or_else(|| Path::new(&self.output).extension().is_none().then_some(Container::Mp4))
+    }
+
+    /// 实际采集的画面尺寸：FFmpeg 设备采集为 `-video_size`（即 --resolution），
+    /// 原生捕获为主显示器尺寸；无法确定时返回 None
+    pub fn capture_size(&self) -> Option<(u32, u32)> {
+        if self.native_capture {
+            native_capture::primary_display_size()
+        } else {
+            self.get_resolution_parts().ok()
+        }
+    }
+
     pub fn get_resolution_parts(&self) -> Result<(u32, u32)> {
         let parts: Vec<&str> = self.resolution.split('x').collect();
         if parts.len() != 2 {
             return Err(RecorderError::InvalidResolution(self.resolution.clone()).into());
         }
 
-        let width: u32 = parts[0].parse()?;
-        let height: u32 = parts[1].parse()?;
+        let parse = |v: &str| {
+            v.parse::<u32>()
+                .map_err(|_| RecorderError::InvalidResolution(self.resolution.clone()))
+        };
+        let width = parse(parts[0])?;
+        let height = parse(parts[1])?;
 
         Ok((width, height))
     }
@@ -143,6 +713,10 @@ impl RecorderConfig {
         }
     }
 
+    pub fn get_audio_input_format(&self) -> &'static str {
+        crate::audio::input_format()
+    }
+
     pub fn get_input_format(&self) -> &'static str {
         #[cfg(windows)]
         {
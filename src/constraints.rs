@@ -0,0 +1,208 @@
+/// 编码约束检查与自动修正：yuv420p 偶数尺寸、编码 level 的分辨率/帧率/码率上限、
+/// 明显偏离推荐值的码率
+///
+/// 默认自动修正并记为警告；严格模式下不做修改，问题作为错误记入校验报告。
+use crate::bitrate::Bitrate;
+use crate::codec::CodecFamily;
+use crate::config::RecorderConfig;
+use crate::validation::ValidationReport;
+
+/// 码率低于推荐下限的该比例、或高于推荐上限的该倍数时视为明显偏离
+const FAR_OUTSIDE_FACTOR: u64 = 2;
+
+/// 码率是否明显偏离推荐范围 `[low, high]`
+pub fn is_far_outside(bitrate: Bitrate, low: Bitrate, high: Bitrate) -> bool {
+    bitrate < low / FAR_OUTSIDE_FACTOR || bitrate > high * FAR_OUTSIDE_FACTOR
+}
+
+/// H.264 level 上限：(level, 每秒宏块数, 每帧宏块数, Main profile 最大码率 kbit/s)
+const H264_LEVELS: &[(&str, u64, u64, u64)] = &[
+    ("1", 1_485, 99, 64),
+    ("1b", 1_485, 99, 128),
+    ("1.1", 3_000, 396, 192),
+    ("1.2", 6_000, 396, 384),
+    ("1.3", 11_880, 396, 768),
+    ("2", 11_880, 396, 2_000),
+    ("2.1", 19_800, 792, 4_000),
+    ("2.2", 20_250, 1_620, 4_000),
+    ("3", 40_500, 1_620, 10_000),
+    ("3.1", 108_000, 3_600, 14_000),
+    ("3.2", 216_000, 5_120, 20_000),
+    ("4", 245_760, 8_192, 20_000),
+    ("4.1", 245_760, 8_192, 50_000),
+    ("4.2", 522_240, 8_704, 50_000),
+    ("5", 589_824, 22_080, 135_000),
+    ("5.1", 983_040, 36_864, 240_000),
+    ("5.2", 2_073_600, 36_864, 240_000),
+    ("6", 4_177_920, 139_264, 240_000),
+    ("6.1", 8_355_840, 139_264, 480_000),
+    ("6.2", 16_711_680, 139_264, 800_000),
+];
+
+/// HEVC level 上限（Main tier）：(level, 每秒亮度采样数, 每帧亮度采样数, 最大码率 kbit/s)
+const HEVC_LEVELS: &[(&str, u64, u64, u64)] = &[
+    ("1", 552_960, 36_864, 128),
+    ("2", 3_686_400, 122_880, 1_500),
+    ("2.1", 7_372_800, 245_760, 3_000),
+    ("3", 16_588_800, 552_960, 6_000),
+    ("3.1", 33_177_600, 983_040, 10_000),
+    ("4", 66_846_720, 2_228_224, 12_000),
+    ("4.1", 133_693_440, 2_228_224, 20_000),
+    ("5", 267_386_880, 8_912_896, 25_000),
+    ("5.1", 534_773_760, 8_912_896, 40_000),
+    ("5.2", 1_069_547_520, 8_912_896, 60_000),
+    ("6", 1_069_547_520, 35_651_584, 60_000),
+    ("6.1", 2_139_095_040, 35_651_584, 120_000),
+    ("6.2", 4_278_190_080, 35_651_584, 240_000),
+];
+
+/// 检查编码约束；`strict` 为 false 时就地修正配置，修正项记为警告
+///
+/// 码率是用户的明确选择，明显偏离推荐范围时默认只提示，`fix_bitrate` 为 true 时才调整。
+pub fn enforce(config: &mut RecorderConfig, strict: bool, fix_bitrate: bool, report: &mut ValidationReport) {
+    // 纯音频模式没有视频编码
+    if config.audio_only {
+        return;
+    }
+
+    let capture = if config.native_capture {
+        // 原生捕获不使用 --resolution，画面为整个显示器，奇数边由滤镜图裁为偶数
+        let Some(size) = config.capture_size() else {
+            return;
+        };
+        size
+    } else {
+        // 分辨率格式错误由 validate 报告
+        let Ok((width, height)) = config.get_resolution_parts() else {
+            return;
+        };
+
+        // yuv420p 色度按 2x2 采样，宽高必须为偶数
+        let (even_w, even_h) = (width & !1, height & !1);
+        if (even_w, even_h) != (width, height) {
+            let reason = "yuv420p 要求宽高均为偶数";
+            if strict || even_w == 0 || even_h == 0 {
+                report
+                    .error("resolution", &config.resolution, reason)
+                    .suggest(format!("改为 {}x{}", even_w.max(2), even_h.max(2)));
+            } else {
+                let fixed = format!("{}x{}", even_w, even_h);
+                report.warning("resolution", &config.resolution, format!("{}，已调整为 {}", reason, fixed));
+                config.resolution = fixed;
+            }
+        }
+        (even_w.max(2), even_h.max(2))
+    };
+    // level 与码率按编码输出的尺寸判断（设置了竖屏等画幅时不同于采集尺寸）
+    let (width, height) = config.orientation.output_size(capture);
+
+    // 先修正码率，level 判断使用修正后的码率
+    check_bitrate(config, width, height, strict, fix_bitrate, report);
+    check_level(config, width, height, strict, report);
+}
+
+/// 检查分辨率 × 帧率 × 码率是否超出 level 上限；未指定 level 时检查是否有任何 level 能满足
+fn check_level(
+    config: &mut RecorderConfig,
+    width: u32,
+    height: u32,
+    strict: bool,
+    report: &mut ValidationReport,
+) {
+    let (table, frame_size, rate) = match config.codec.family() {
+        CodecFamily::H264 => {
+            let mbs = (width as u64).div_ceil(16) * (height as u64).div_ceil(16);
+            (H264_LEVELS, mbs, mbs * config.fps as u64)
+        }
+        CodecFamily::Hevc => {
+            let samples = width as u64 * height as u64;
+            (HEVC_LEVELS, samples, samples * config.fps as u64)
+        }
+        CodecFamily::Vp9 | CodecFamily::Av1 => return,
+    };
+
+    // 恒定质量模式下码率不可预知，不参与 level 码率判断
+    let bitrate_kbps = (!config.rate_control.is_constant_quality()).then(|| config.bitrate.kbps() as u64);
+    let fits = |&(_, max_rate, max_frame, max_br): &(&str, u64, u64, u64)| {
+        frame_size <= max_frame && rate <= max_rate && bitrate_kbps.is_none_or(|br| br <= max_br)
+    };
+    let lowest_fitting = table.iter().find(|l| fits(l)).map(|l| l.0);
+
+    let Some(level) = config.tuning.level.clone() else {
+        if lowest_fitting.is_none() {
+            report
+                .error(
+                    "resolution",
+                    format!("{}x{}@{}fps", width, height, config.fps),
+                    format!("超出 {} 所有 level 的能力上限", config.codec),
+                )
+                .suggest("降低分辨率、帧率或码率，或改用 HEVC/AV1");
+        }
+        return;
+    };
+
+    // 未知 level 由 tuning 校验报告
+    let Some(entry) = table.iter().find(|l| l.0 == level) else {
+        return;
+    };
+    if fits(entry) {
+        return;
+    }
+
+    let reason = format!(
+        "不支持 {}x{}@{}fps{}",
+        width,
+        height,
+        config.fps,
+        bitrate_kbps.map(|br| format!(" / {}k", br)).unwrap_or_default()
+    );
+    match lowest_fitting {
+        Some(fixed) if !strict => {
+            report.warning("level", &level, format!("{}，已提升为 {}", reason, fixed));
+            config.tuning.level = Some(fixed.to_string());
+        }
+        Some(fixed) => {
+            report.error("level", &level, reason).suggest(format!("至少需要 level {}", fixed));
+        }
+        None => {
+            report
+                .error("level", &level, format!("{}，且没有任何 level 能满足", reason))
+                .suggest("降低分辨率、帧率或码率");
+        }
+    }
+}
+
+/// 码率明显偏离推荐范围：严格模式报错，`fix` 为 true 时修正到范围边界，否则保留并提示
+fn check_bitrate(
+    config: &mut RecorderConfig,
+    width: u32,
+    height: u32,
+    strict: bool,
+    fix: bool,
+    report: &mut ValidationReport,
+) {
+    if config.rate_control.is_constant_quality() {
+        return;
+    }
+
+    let (low, high) = Bitrate::recommended_range(width, height, config.fps, config.codec.efficiency());
+    if !is_far_outside(config.bitrate, low, high) {
+        return;
+    }
+    let target = if config.bitrate < low { low } else { high };
+
+    let reason = format!(
+        "明显偏离 {}x{}@{}fps 的推荐范围 ({} - {})",
+        width, height, config.fps, low, high
+    );
+    if strict {
+        report.error("bitrate", config.bitrate, reason).suggest(format!("改为 {}", target));
+    } else if fix {
+        report.warning("bitrate", config.bitrate, format!("{}，已调整为 {}", reason, target));
+        config.bitrate = target;
+    } else {
+        report
+            .warning("bitrate", config.bitrate, reason)
+            .suggest(format!("改为 {}，或加 --fix-bitrate 自动调整", target));
+    }
+}
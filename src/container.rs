@@ -0,0 +1,171 @@
+/// 输出容器模型：根据扩展名或 --format 确定封装格式，并校验编码器与容器的兼容性
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::audio::AudioCodec;
+use crate::codec::{CodecFamily, VideoCodec};
+use crate::error::RecorderError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    Mp4,
+    Mkv,
+    Mov,
+    Webm,
+    Ts,
+    Flv,
+    /// 纯音频：AAC in MP4 (iPod 封装)
+    M4a,
+    /// 纯音频：Opus / FLAC
+    Ogg,
+    /// 纯音频：MP3
+    Mp3,
+    /// 纯音频：FLAC
+    Flac,
+}
+
+impl Container {
+    pub const ALL: [Container; 10] = [
+        Container::Mp4,
+        Container::Mkv,
+        Container::Mov,
+        Container::Webm,
+        Container::Ts,
+        Container::Flv,
+        Container::M4a,
+        Container::Ogg,
+        Container::Mp3,
+        Container::Flac,
+    ];
+
+    /// 根据输出文件扩展名推断容器
+    pub fn from_path(path: &str) -> Option<Container> {
+        let ext = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "mp4" | "m4v" => Some(Container::Mp4),
+            "mkv" => Some(Container::Mkv),
+            "mov" => Some(Container::Mov),
+            "webm" => Some(Container::Webm),
+            "ts" | "m2ts" | "mts" => Some(Container::Ts),
+            "flv" => Some(Container::Flv),
+            "m4a" => Some(Container::M4a),
+            "ogg" | "oga" | "opus" => Some(Container::Ogg),
+            "mp3" => Some(Container::Mp3),
+            "flac" => Some(Container::Flac),
+            _ => None,
+        }
+    }
+
+    /// 用户可见的名称（同时也是 --format 接受的值）
+    pub fn name(&self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Mkv => "mkv",
+            Container::Mov => "mov",
+            Container::Webm => "webm",
+            Container::Ts => "ts",
+            Container::Flv => "flv",
+            Container::M4a => "m4a",
+            Container::Ogg => "ogg",
+            Container::Mp3 => "mp3",
+            Container::Flac => "flac",
+        }
+    }
+
+    /// FFmpeg muxer 名称（-f 参数）
+    pub fn muxer(&self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Mkv => "matroska",
+            Container::Mov => "mov",
+            Container::Webm => "webm",
+            Container::Ts => "mpegts",
+            Container::Flv => "flv",
+            Container::M4a => "ipod",
+            Container::Ogg => "ogg",
+            Container::Mp3 => "mp3",
+            Container::Flac => "flac",
+        }
+    }
+
+    /// 是否为 ISO BMFF（MP4/MOV/M4A）系列，moov 索引在文件尾部写入
+    pub fn is_isobmff(&self) -> bool {
+        matches!(self, Container::Mp4 | Container::Mov | Container::M4a)
+    }
+
+    /// 是否为只能容纳音频的格式
+    pub fn is_audio_only(&self) -> bool {
+        matches!(self, Container::M4a | Container::Ogg | Container::Mp3 | Container::Flac)
+    }
+
+    /// 普通（非崩溃安全）文件输出所需的格式参数
+    pub fn output_args(&self) -> Vec<&'static str> {
+        let mut args = vec!["-f", self.muxer()];
+        if self.is_isobmff() {
+            // moov 前置，便于边下载边播放
+            args.extend(["-movflags", "+faststart"]);
+        }
+        args
+    }
+
+    /// 检查视频编码器能否放入该容器
+    pub fn supports_video_codec(&self, codec: VideoCodec) -> bool {
+        let family = codec.family();
+        match self {
+            Container::Mkv => true,
+            Container::Mp4 => matches!(
+                family,
+                CodecFamily::H264 | CodecFamily::Hevc | CodecFamily::Vp9 | CodecFamily::Av1
+            ),
+            Container::Mov | Container::Ts => matches!(family, CodecFamily::H264 | CodecFamily::Hevc),
+            Container::Webm => matches!(family, CodecFamily::Vp9 | CodecFamily::Av1),
+            Container::Flv => family == CodecFamily::H264,
+            Container::M4a | Container::Ogg | Container::Mp3 | Container::Flac => false,
+        }
+    }
+
+    /// 检查音频编码器能否放入该容器
+    pub fn supports_audio_codec(&self, codec: AudioCodec) -> bool {
+        match self {
+            Container::Mkv => true,
+            Container::Mp4 => codec != AudioCodec::Pcm,
+            Container::Mov => matches!(codec, AudioCodec::Aac | AudioCodec::Mp3 | AudioCodec::Pcm),
+            Container::Webm => codec == AudioCodec::Opus,
+            Container::Ts => matches!(codec, AudioCodec::Aac | AudioCodec::Mp3 | AudioCodec::Opus),
+            Container::Flv => matches!(codec, AudioCodec::Aac | AudioCodec::Mp3),
+            Container::M4a => codec == AudioCodec::Aac,
+            Container::Ogg => matches!(codec, AudioCodec::Opus | AudioCodec::Flac),
+            Container::Mp3 => codec == AudioCodec::Mp3,
+            Container::Flac => codec == AudioCodec::Flac,
+        }
+    }
+
+    /// 该容器下的默认音频编码器
+    pub fn default_audio_codec(&self) -> AudioCodec {
+        match self {
+            Container::Webm | Container::Ogg => AudioCodec::Opus,
+            Container::Mp3 => AudioCodec::Mp3,
+            Container::Flac => AudioCodec::Flac,
+            _ => AudioCodec::Aac,
+        }
+    }
+}
+
+impl fmt::Display for Container {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Container {
+    type Err = RecorderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        Container::ALL
+            .into_iter()
+            .find(|c| c.name() == lower || c.muxer() == lower)
+            .ok_or_else(|| RecorderError::UnsupportedContainer(s.to_string()))
+    }
+}
@@ -3,17 +3,60 @@ use thiserror::Error;
 #[allow(dead_code)]
 #[derive(Error, Debug)]
 pub enum RecorderError {
-    #[error("无效的分辨率格式: {0}，应为 WIDTHxHEIGHT")]
+    #[error("无效的分辨率格式: {0}")]
     InvalidResolution(String),
 
-    #[error("无效的 FPS: {0}，应在 1-120 之间")]
+    #[error("无效的 FPS: {0}")]
     InvalidFps(u32),
 
     #[error("不支持的编码器: {0}")]
     UnsupportedCodec(String),
 
-    #[error("无效的流地址: {0}，应以 rtmp:// 开头")]
-    InvalidStreamUrl(String),
+    #[error("不支持的音频编码器: {0}")]
+    UnsupportedAudioCodec(String),
+
+    #[error("当前 FFmpeg 未提供编码器 {encoder}")]
+    EncoderUnavailable { encoder: String, alternatives: Vec<String> },
+
+    /// `scheme` 为期望的协议（rtmp / rtsp）
+    #[error("{url} 不是有效的 {scheme} 流地址")]
+    InvalidStreamUrl { url: String, scheme: &'static str },
+
+    #[error("不支持的容器格式: {0}")]
+    UnsupportedContainer(String),
+
+    #[error("编码器 {codec} 不能封装到 {container} 容器")]
+    IncompatibleCodec { codec: String, container: String },
+
+    #[error("无效的码率: {0}")]
+    InvalidBitrate(String),
+
+    #[error("码率 {bitrate} 超出允许范围")]
+    BitrateOutOfRange { bitrate: String, min: String, max: String },
+
+    #[error("无效的码率控制设置: {0}")]
+    InvalidRateControl(String),
+
+    #[error("无效的编码器参数: {0}")]
+    InvalidEncoderOption(String),
+
+    #[error("不支持的降噪方式: {0}")]
+    UnsupportedDenoise(String),
+
+    #[error("当前 FFmpeg 未提供滤镜 {0}")]
+    FilterUnavailable(String),
+
+    #[error("无效的画面叠加设置: {0}")]
+    InvalidOverlay(String),
+
+    #[error("无效的遮挡区域: {0}")]
+    InvalidMask(String),
+
+    #[error("无效的输出方向设置: {0}")]
+    InvalidOrientation(String),
+
+    #[error("配置校验未通过，共 {0} 个错误")]
+    InvalidConfig(usize),
 
     #[error("输出路径为空")]
     EmptyOutput,
@@ -30,9 +73,52 @@ pub enum RecorderError {
     #[error("推流错误: {0}")]
     StreamError(String),
 
+    #[error("{label}已静音 {seconds} 秒，终止会话")]
+    SilenceDetected { label: String, seconds: u64 },
+
     #[error("IO 错误: {0}")]
     IoError(#[from] std::io::Error),
 
     #[error("系统错误: {0}")]
     SystemError(String),
 }
+
+impl RecorderError {
+    /// 面向用户的修改建议，用于校验报告
+    pub fn suggestion(&self) -> Option<String> {
+        let s = match self {
+            RecorderError::InvalidResolution(_) => "格式为 WIDTHxHEIGHT，例如 1920x1080".to_string(),
+            RecorderError::InvalidFps(_) => "取 1-120 之间的值".to_string(),
+            RecorderError::UnsupportedCodec(_) => {
+                "可选 h264, h265, nvenc, hevc_nvenc, qsv, vp9, av1, libaom-av1".to_string()
+            }
+            RecorderError::UnsupportedAudioCodec(_) => "可选 aac, opus, mp3, flac, pcm".to_string(),
+            RecorderError::EncoderUnavailable { alternatives, .. } if alternatives.is_empty() => {
+                "安装包含该编码器的 FFmpeg，或通过 --ffmpeg-path 指定".to_string()
+            }
+            RecorderError::EncoderUnavailable { alternatives, .. } => {
+                format!("改用 {}", alternatives.join(", "))
+            }
+            RecorderError::InvalidStreamUrl { scheme, .. } => format!(
+                "应以 {0}:// 或 {0}s:// 开头，例如 {0}://localhost{1}/live/stream",
+                scheme,
+                if *scheme == "rtsp" { ":8554" } else { "" }
+            ),
+            RecorderError::UnsupportedContainer(_) => {
+                "使用 mp4, mkv, mov, webm, ts, flv（纯音频: m4a, ogg, mp3, flac）扩展名，或通过 --format 指定".to_string()
+            }
+            RecorderError::IncompatibleCodec { .. } => "更换编码器，或改用兼容性最广的 mkv 容器".to_string(),
+            RecorderError::InvalidBitrate(_) => "使用 5000k、5M 或 5000000 这样的写法".to_string(),
+            RecorderError::BitrateOutOfRange { min, max, .. } => format!("取 {} - {} 之间的值", min, max),
+            RecorderError::UnsupportedDenoise(_) => "可选 afftdn（无需模型）, arnndn（需要 RNNoise 模型）".to_string(),
+            RecorderError::InvalidMask(_) => "格式为 X,Y,W,H[:blur|pixelate|fill[=颜色]]，例如 100,200,400,300:pixelate".to_string(),
+            RecorderError::FilterUnavailable(_) => "使用完整版 FFmpeg（如 gyan.dev full build），或通过 --ffmpeg-path 指定".to_string(),
+            RecorderError::SilenceDetected { .. } => {
+                "检查麦克风是否被静音，或调低 --silence-threshold / 调大 --silence-timeout".to_string()
+            }
+            RecorderError::EmptyOutput => "通过 -o 指定输出文件或流地址".to_string(),
+            _ => return None,
+        };
+        Some(s)
+    }
+}
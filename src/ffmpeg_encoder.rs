@@ -1,19 +1,438 @@
 use anyhow::Result;
-use log::info;
+use log::{info, warn};
+use std::collections::BTreeSet;
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-pub fn init_ffmpeg() -> Result<()> {
+use crate::audio::{self, AudioDevice, AudioLayout, ChainOptions};
+use crate::config::{RecorderConfig, StreamProtocol};
+use crate::error::RecorderError;
+use crate::filter_graph::{FilterGraph, Pad};
+use crate::level_meter::LevelMeter;
+use crate::recovery;
+
+/// 指定 FFmpeg 可执行文件路径的环境变量
+pub const FFMPEG_PATH_ENV: &str = "FFMPEG_PATH";
+
+/// 已安装 FFmpeg 的位置、版本与能力（编码器、封装器、输入设备、滤镜）
+#[derive(Debug, Clone)]
+pub struct FfmpegCapabilities {
+    pub path: PathBuf,
+    /// 版本号，例如 `6.1.1` 或 `N-112345-gabcdef`
+    pub version: String,
+    /// 编译配置参数，例如 `--enable-libx264`
+    pub configuration: Vec<String>,
+    pub encoders: BTreeSet<String>,
+    pub muxers: BTreeSet<String>,
+    pub input_devices: BTreeSet<String>,
+    pub filters: BTreeSet<String>,
+}
+
+impl FfmpegCapabilities {
+    /// 创建一个使用该 FFmpeg 可执行文件的命令
+    pub fn command(&self) -> Command {
+        Command::new(&self.path)
+    }
+
+    pub fn has_encoder(&self, name: &str) -> bool {
+        self.encoders.contains(name)
+    }
+
+    pub fn has_muxer(&self, name: &str) -> bool {
+        self.muxers.contains(name)
+    }
+
+    pub fn has_input_device(&self, name: &str) -> bool {
+        self.input_devices.contains(name)
+    }
+
+    pub fn has_filter(&self, name: &str) -> bool {
+        self.filters.contains(name)
+    }
+
+    /// 检查滤镜是否可用，缺失时返回 `FilterUnavailable`
+    pub fn ensure_filter(&self, name: &str) -> Result<(), RecorderError> {
+        if self.has_filter(name) {
+            Ok(())
+        } else {
+            Err(RecorderError::FilterUnavailable(name.to_string()))
+        }
+    }
+
+    /// 版本是否不低于 `major.minor`
+    ///
+    /// 从 git 构建的版本（`N-xxxxx`）视为最新；无法识别的版本号按旧版本处理。
+    pub fn version_at_least(&self, major: u32, minor: u32) -> bool {
+        let version = self.version.trim_start_matches('n');
+        if version.starts_with("N-") {
+            return true;
+        }
+        let mut parts = version.split(|c: char| !c.is_ascii_digit()).map(|p| p.parse::<u32>().ok());
+        match (parts.next().flatten(), parts.next().flatten()) {
+            (Some(maj), min) => (maj, min.unwrap_or(0)) >= (major, minor),
+            (None, _) => false,
+        }
+    }
+
+    /// 是否在编译时启用了某项功能，例如 `libfreetype`
+    pub fn has_build_flag(&self, feature: &str) -> bool {
+        self.configuration.contains(&format!("--enable-{}", feature))
+    }
+}
+
+/// 定位 FFmpeg 并探测其能力
+///
+/// 查找顺序：`--ffmpeg-path` 参数 → `FFMPEG_PATH` 环境变量 → 程序所在目录 → PATH。
+pub fn init_ffmpeg(explicit_path: Option<&str>) -> Result<FfmpegCapabilities> {
     info!("正在初始化 FFmpeg...");
-    info!("✅ FFmpeg 初始化成功");
+
+    let path = locate_ffmpeg(explicit_path)?;
+    let version_output = run_ffmpeg(&path, &["-version"])?;
+    let (version, configuration) = parse_version(&version_output);
+
+    let caps = FfmpegCapabilities {
+        encoders: parse_listing(&run_ffmpeg(&path, &["-hide_banner", "-encoders"])?, |_| true),
+        muxers: parse_listing(&run_ffmpeg(&path, &["-hide_banner", "-muxers"])?, |f| f.contains('E')),
+        input_devices: parse_listing(&run_ffmpeg(&path, &["-hide_banner", "-devices"])?, |f| f.contains('D')),
+        filters: parse_filters(&run_ffmpeg(&path, &["-hide_banner", "-filters"])?),
+        path,
+        version,
+        configuration,
+    };
+
+    info!("✅ FFmpeg 初始化成功: {} (版本 {})", caps.path.display(), caps.version);
+    log::debug!(
+        "FFmpeg 能力: {} 个编码器, {} 个封装器, {} 个滤镜, 输入设备: {:?}",
+        caps.encoders.len(),
+        caps.muxers.len(),
+        caps.filters.len(),
+        caps.input_devices
+    );
+    log::debug!("FFmpeg 编译配置: {}", caps.configuration.join(" "));
+    Ok(caps)
+}
+
+fn locate_ffmpeg(explicit_path: Option<&str>) -> Result<PathBuf> {
+    if let Some(p) = explicit_path {
+        let path = PathBuf::from(p);
+        if !path.is_file() {
+            return Err(RecorderError::FFmpegInitError(format!("--ffmpeg-path 指定的文件不存在: {}", p)).into());
+        }
+        return Ok(path);
+    }
+
+    if let Some(p) = env::var_os(FFMPEG_PATH_ENV).filter(|p| !p.is_empty()) {
+        let path = PathBuf::from(p);
+        if !path.is_file() {
+            return Err(RecorderError::FFmpegInitError(format!(
+                "环境变量 {} 指向的文件不存在: {}",
+                FFMPEG_PATH_ENV,
+                path.display()
+            ))
+            .into());
+        }
+        return Ok(path);
+    }
+
+    let exe_name = if cfg!(windows) { "ffmpeg.exe" } else { "ffmpeg" };
+
+    // 发布包中 ffmpeg 可能与本程序放在同一目录
+    let beside_exe = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(exe_name)));
+    let search_path = env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).map(|dir| dir.join(exe_name)).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    beside_exe
+        .into_iter()
+        .chain(search_path)
+        .find(|candidate| candidate.is_file())
+        .ok_or_else(|| {
+            RecorderError::FFmpegInitError(format!(
+                "未找到 {}，请安装 FFmpeg 并加入 PATH，或通过 --ffmpeg-path / {} 指定",
+                exe_name, FFMPEG_PATH_ENV
+            ))
+            .into()
+        })
+}
+
+fn run_ffmpeg(path: &Path, args: &[&str]) -> Result<String> {
+    let out = Command::new(path)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| RecorderError::FFmpegInitError(format!("无法执行 {}: {}", path.display(), e)))?;
+
+    if !out.status.success() {
+        return Err(RecorderError::FFmpegInitError(format!(
+            "{} {} 执行失败: {}",
+            path.display(),
+            args.join(" "),
+            out.status
+        ))
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// 解析 `ffmpeg -version`：首行 `ffmpeg version X ...`，以及 `configuration:` 行
+fn parse_version(output: &str) -> (String, Vec<String>) {
+    let version = output
+        .lines()
+        .next()
+        .and_then(|l| l.strip_prefix("ffmpeg version "))
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap_or("unknown")
+        .to_string();
+
+    let configuration = output
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("configuration:"))
+        .map(|c| c.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default();
+
+    (version, configuration)
+}
+
+/// 解析 `-encoders` / `-muxers` / `-devices` 列表
+///
+/// 说明部分以 `--` 或 `------` 分隔线结束，之后每行为 `<标志> <名称> <描述>`，
+/// `keep` 根据标志列决定是否保留该项。
+fn parse_listing(output: &str, keep: impl Fn(&str) -> bool) -> BTreeSet<String> {
+    output
+        .lines()
+        .skip_while(|l| !l.trim_start().starts_with("--"))
+        .skip(1)
+        .filter_map(|l| {
+            let mut parts = l.split_whitespace();
+            let flags = parts.next()?;
+            let name = parts.next()?;
+            keep(flags).then_some(name)
+        })
+        .flat_map(|names| names.split(','))
+        .map(str::to_string)
+        .collect()
+}
+
+/// 解析 `-filters` 列表：说明部分没有分隔线，每个滤镜一行 `<标志> <名称> <输入->输出> <描述>`
+fn parse_filters(output: &str) -> BTreeSet<String> {
+    output
+        .lines()
+        .filter_map(|l| {
+            let mut parts = l.split_whitespace();
+            let _flags = parts.next()?;
+            let name = parts.next()?;
+            parts.next()?.contains("->").then(|| name.to_string())
+        })
+        .collect()
+}
+
+/// 添加文件输出的容器参数与输出路径（崩溃安全模式下写入分片中间文件）
+pub fn push_file_output(cmd: &mut Command, config: &RecorderConfig) {
+    let Some(container) = config.container() else {
+        // 未识别的扩展名：不指定 -f，由 FFmpeg 根据扩展名选择封装
+        cmd.arg("-y").arg(&config.output);
+        return;
+    };
+
+    if let Some(partial) = recovery::partial_output(config) {
+        // 崩溃安全：写入分片 MP4，中断后仍可播放
+        cmd.arg("-f").arg(container.muxer());
+        cmd.arg("-movflags").arg(recovery::FRAGMENTED_MOVFLAGS);
+        cmd.arg("-y").arg(partial);
+    } else {
+        cmd.args(container.output_args());
+        cmd.arg("-y").arg(&config.output);
+    }
+}
+
+/// 根据协议添加输出相关参数与输出地址
+pub fn push_output(cmd: &mut Command, config: &RecorderConfig) {
+    cmd.args(config.low_latency_output_args());
+    match config.protocol {
+        StreamProtocol::RTMP => {
+            // RTMP 使用 FLV 容器
+            cmd.arg("-f").arg("flv");
+            cmd.arg("-flvflags").arg("no_duration_filesize");
+            cmd.arg("-rtmp_live").arg("live");
+            cmd.arg(&config.output);
+        }
+        StreamProtocol::RTSP => {
+            // RTSP 推流，使用 TCP 传输以提高可靠性
+            cmd.arg("-rtsp_transport").arg("tcp");
+            cmd.arg("-f").arg("rtsp");
+            cmd.arg(&config.output);
+        }
+        StreamProtocol::File => {
+            // 容器由扩展名或 --format 决定，并覆盖已存在的文件
+            push_file_output(cmd, config);
+        }
+    }
+}
+
+/// 纯音频模式的 FFmpeg 命令：不采集屏幕，音频源从第 0 路输入开始
+pub fn build_audio_only_command(config: &RecorderConfig) -> Command {
+    let mut cmd = config.ffmpeg.command();
+    push_audio_inputs(&mut cmd, config);
+    let mut graph = FilterGraph::default();
+    let audio = compose_audio(config, &mut graph, 0);
+    push_graph(&mut cmd, &graph, None, &audio);
+    push_audio_encoder(&mut cmd, config);
+    push_output(&mut cmd, config);
+    cmd
+}
+
+/// 添加全部音频输入（设备是否存在已在配置校验时探测）
+///
+/// 设置了 `--av-offset` 时通过 `-itsoffset` 平移音频时间戳，纯音频模式下没有视频可对齐，不做平移。
+pub fn push_audio_inputs(cmd: &mut Command, config: &RecorderConfig) {
+    let input_format = config.get_audio_input_format();
+    let offset = (!config.audio_only && config.av_offset != 0.0).then(|| config.av_offset.to_string());
+    for source in &config.audio_sources {
+        // 实时设备输入需要足够的队列，避免视频编码繁忙时丢失音频样本
+        cmd.arg("-thread_queue_size").arg("1024");
+        if let Some(offset) = &offset {
+            cmd.arg("-itsoffset").arg(offset);
+        }
+        cmd.args(source.input_args(input_format));
+    }
+}
+
+/// 画中画与水印输入，依次位于全部音频输入之后
+pub fn push_video_inputs(cmd: &mut Command, config: &RecorderConfig) {
+    if let Some(pip) = &config.pip {
+        cmd.args(pip.input_args());
+    }
+    if let Some(watermark) = &config.watermark {
+        cmd.args(watermark.input_args());
+    }
+}
+
+/// 将音频源加入滤镜图，返回各音轨的输出端口；`first_input` 为第一个音频输入的序号
+pub fn compose_audio(config: &RecorderConfig, graph: &mut FilterGraph, first_input: usize) -> Vec<Pad> {
+    let mic_filters = config.audio_filters.chain();
+    let options = ChainOptions {
+        mic_filters: mic_filters.as_deref(),
+        metering: config.level_metering(),
+        runtime_volume: config.audio_control,
+    };
+    audio::compose(&config.audio_sources, config.audio_layout, first_input, &options, graph)
+}
+
+/// 输出滤镜图，并按顺序映射视频（纯音频模式为 None）与各音轨的输出端口
+pub fn push_graph(cmd: &mut Command, graph: &FilterGraph, video: Option<&Pad>, audio: &[Pad]) {
+    if !graph.is_empty() {
+        cmd.arg("-filter_complex").arg(graph.to_string());
+    }
+    for pad in video.into_iter().chain(audio) {
+        cmd.arg("-map").arg(pad.map_arg());
+    }
+}
+
+/// 音轨元数据与音频编码参数（未启用音频时不输出）
+pub fn push_audio_encoder(cmd: &mut Command, config: &RecorderConfig) {
+    let sources = &config.audio_sources;
+    if sources.is_empty() {
+        return;
+    }
+    // 混音后多个音频源合为一条音轨，不再逐轨标注
+    if sources.len() == 1 || config.audio_layout == AudioLayout::Separate {
+        let isobmff = config.container().is_some_and(|c| c.is_isobmff());
+        cmd.args(audio::track_metadata_args(sources, isobmff));
+    }
+    cmd.args(config.audio_encoder_args());
+}
+
+/// 在后台线程中将 FFmpeg stderr 逐行输出到日志
+///
+/// stderr 被管道接管后必须持续读取，否则缓冲区写满会导致 FFmpeg 阻塞。
+/// 传入电平监测时，电平统计行交给监测解析，不写入日志。
+pub fn log_stderr(child: &mut Child, meter: Option<Arc<LevelMeter>>) {
+    if let Some(stderr) = child.stderr.take() {
+        thread::spawn(move || {
+            let reader = BufReader::new(stderr);
+            for line in reader.lines().map_while(Result::ok) {
+                if meter.as_ref().is_some_and(|m| m.handle_line(&line)) {
+                    continue;
+                }
+                log::info!("ffmpeg | {}", line);
+            }
+        });
+    }
+}
+
+/// 发送停止指令后等待 FFmpeg 写完文件的最长时间
+pub const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 优雅地停止 FFmpeg：向 stdin 发送 `q`，让其写完文件尾部后自行退出；
+/// 超时仍未退出则强制结束进程
+///
+/// 在异步任务中调用，轮询间隔使用 tokio 的定时器，不阻塞运行时的工作线程。
+pub async fn stop_gracefully(child: &mut Child, timeout: Duration) -> Result<()> {
+    if let Some(mut stdin) = child.stdin.take() {
+        // FFmpeg 可能已经因 Ctrl+C 自行退出，此时写入失败可以忽略
+        let _ = stdin.write_all(b"q");
+        let _ = stdin.flush();
+    }
+
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if child.try_wait()?.is_some() {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    warn!("FFmpeg 在 {:?} 内未退出，强制结束", timeout);
+    child.kill()?;
+    child.wait()?;
     Ok(())
 }
 
-/// 简单探测音频设备是否存在（Windows 上使用 dshow 列表探测）
-pub fn probe_audio_device(device: &str) -> bool {
-    use std::process::Command;
+/// 通过 `ffmpeg -sources` 列出音频输入设备（PulseAudio/PipeWire-pulse 的 source 与 monitor）
+pub fn list_audio_devices(ffmpeg: &FfmpegCapabilities, input_format: &str) -> Result<Vec<AudioDevice>> {
+    let output = run_ffmpeg(&ffmpeg.path, &["-hide_banner", "-sources", input_format])?;
+    Ok(parse_sources(&output))
+}
+
+/// 解析 `-sources` 输出：`Auto-detected sources for pulse:` 之后每行为
+/// `[*] <名称> [<描述>]`，`*` 标记默认设备，新版本末尾还带有 `(audio)` 等媒体类型
+fn parse_sources(output: &str) -> Vec<AudioDevice> {
+    output
+        .lines()
+        .skip_while(|l| !l.starts_with("Auto-detected sources"))
+        .skip(1)
+        .filter_map(|l| {
+            let is_default = l.starts_with('*');
+            let rest = l.trim_start_matches('*').trim();
+            let (name, description) = match rest.split_once(" [") {
+                Some((name, tail)) => (name, tail.split_once(']').map_or(tail, |(d, _)| d)),
+                None => (rest, ""),
+            };
+            (!name.is_empty()).then(|| AudioDevice {
+                name: name.to_string(),
+                description: description.to_string(),
+                is_default,
+            })
+        })
+        .collect()
+}
+
+/// 探测音频设备是否存在
+///
+/// Windows 在 dshow 设备列表中查找；Linux 通过 PulseAudio 的设备列表查找，
+/// 无法连接音频服务时视为不存在；macOS 的 avfoundation 不支持枚举，始终返回 true。
+pub fn probe_audio_device(ffmpeg: &FfmpegCapabilities, device: &str) -> bool {
     #[cfg(windows)]
     {
         // ffmpeg 列出 dshow 设备信息在 stderr
-        let out = Command::new("ffmpeg")
+        let out = ffmpeg.command()
             .args(["-list_devices", "true", "-f", "dshow", "-i", "dummy"])
             .output();
 
@@ -21,12 +440,23 @@ pub fn probe_audio_device(device: &str) -> bool {
             let stderr = String::from_utf8_lossy(&o.stderr).to_lowercase();
             return stderr.contains(&device.to_lowercase());
         }
-        return false;
+        false
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        match list_audio_devices(ffmpeg, "pulse") {
+            Ok(devices) => audio::device_exists(&devices, device),
+            Err(e) => {
+                warn!("无法列出 PulseAudio 音频设备: {}", e);
+                false
+            }
+        }
     }
 
-    // 非 Windows 平台：保守策略，返回 true（或未来可实现更严格的探测）
-    #[cfg(not(windows))]
+    #[cfg(not(any(windows, target_os = "linux")))]
     {
+        let _ = (ffmpeg, device);
         true
     }
 }
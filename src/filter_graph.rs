@@ -0,0 +1,360 @@
+/// FFmpeg 滤镜图：以带标签的连接组合多路输入与滤镜链，生成 `-filter_complex`
+///
+/// 各功能（遮挡、缩放、画中画、文字叠加、水印、音频处理）只需接收上一阶段的输出端口，
+/// 追加自己的滤镜链并返回新的端口；最后由命令构建函数统一输出滤镜图与 `-map`。
+use std::fmt;
+
+use crate::config::RecorderConfig;
+use crate::privacy_mask;
+use crate::watermark::Watermark;
+
+/// 滤镜图中的端口：命令行输入的某类流（如 `0:v`），或滤镜链输出的标签
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pad {
+    Input(String),
+    Label(String),
+}
+
+impl Pad {
+    /// 第 `index` 路输入的视频流
+    pub fn video_input(index: usize) -> Self {
+        Pad::Input(format!("{}:v", index))
+    }
+
+    /// 第 `index` 路输入的音频流
+    pub fn audio_input(index: usize) -> Self {
+        Pad::Input(format!("{}:a", index))
+    }
+
+    /// `-map` 的参数：输入流不加方括号，标签加方括号
+    pub fn map_arg(&self) -> String {
+        match self {
+            Pad::Input(spec) => spec.clone(),
+            Pad::Label(label) => format!("[{}]", label),
+        }
+    }
+}
+
+impl fmt::Display for Pad {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pad::Input(spec) => write!(f, "[{}]", spec),
+            Pad::Label(label) => write!(f, "[{}]", label),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FilterGraph {
+    chains: Vec<String>,
+    labels: usize,
+}
+
+impl FilterGraph {
+    pub fn is_empty(&self) -> bool {
+        self.chains.is_empty()
+    }
+
+    /// 生成不重复的标签，`name` 仅用于提高命令的可读性
+    fn label(&mut self, name: &str) -> Pad {
+        self.labels += 1;
+        Pad::Label(format!("{}{}", name, self.labels))
+    }
+
+    /// 将 `inputs` 经过逗号串联的 `filters` 连接到新的输出端口
+    ///
+    /// 单路输入且没有滤镜时原样返回输入端口，不生成空链。
+    pub fn link<S: AsRef<str>>(&mut self, inputs: &[&Pad], filters: &[S], name: &str) -> Pad {
+        if filters.is_empty() {
+            if let [input] = inputs {
+                return (*input).clone();
+            }
+        }
+        let output = self.label(name);
+        let inputs: String = inputs.iter().map(|p| p.to_string()).collect();
+        let filters: Vec<&str> = filters.iter().map(AsRef::as_ref).collect();
+        self.chains.push(format!("{}{}{}", inputs, filters.join(","), output));
+        output
+    }
+
+    /// 没有输入的源滤镜链（如 color）
+    pub fn source<S: AsRef<str>>(&mut self, filters: &[S], name: &str) -> Pad {
+        self.link(&[], filters, name)
+    }
+
+    /// 复制一路视频，返回两个内容相同的端口
+    pub fn split(&mut self, input: &Pad, name: &str) -> (Pad, Pad) {
+        let first = self.label(name);
+        let second = self.label(name);
+        self.chains.push(format!("{}split{}{}", input, first, second));
+        (first, second)
+    }
+}
+
+impl fmt::Display for FilterGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.chains.join(";"))
+    }
+}
+
+/// 视频画面的来源，决定滤镜图中由 FFmpeg 完成的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoSource {
+    /// FFmpeg 采集设备（gdigrab / x11grab 等）：遮挡、缩放与水印均在滤镜图中完成
+    Device,
+    /// 原生捕获经管道传入的 BGRA 帧：遮挡（及未调整方向时的水印）已在程序内处理，不缩放
+    Pipe { width: usize, height: usize },
+}
+
+/// 原生捕获在程序内逐帧合成的水印
+///
+/// 调整了输出方向时画面会被裁剪或旋转，水印改为 FFmpeg 输入，在滤镜图最后合成。
+pub fn native_watermark(config: &RecorderConfig) -> Option<&Watermark> {
+    config.watermark.as_ref().filter(|_| config.orientation.is_empty())
+}
+
+/// 构建视频滤镜链：遮挡 → 输出方向或缩放 → 像素格式 → 画中画 → 时间戳与文字 → 水印
+///
+/// 额外的视频输入（画中画、水印）依次位于全部音频输入之后。
+pub fn video(config: &RecorderConfig, graph: &mut FilterGraph, source: VideoSource) -> Pad {
+    let mut video = Pad::video_input(0);
+    let mut next_input = 1 + config.audio_sources.len();
+
+    let capture = match source {
+        VideoSource::Device => {
+            video = privacy_mask::apply_graph(&config.masks, graph, video);
+            config.get_resolution_parts().ok()
+        }
+        VideoSource::Pipe { width, height } => Some((width as u32, height as u32)),
+    };
+
+    let mut filters = Vec::new();
+    if !config.orientation.is_empty() {
+        // 输出方向决定最终尺寸，取代缩放与偶数裁剪
+        video = config.orientation.compose(graph, video, capture);
+    } else if source == VideoSource::Device {
+        // 先缩放到目标分辨率再转换像素格式，避免编码器使用不兼容的色彩空间
+        if let Some((w, h)) = capture {
+            filters.push(format!("scale={}:{}:flags=lanczos", w, h));
+        }
+    } else if let Some((width, height)) = capture.filter(|(w, h)| w % 2 != 0 || h % 2 != 0) {
+        // yuv420p 要求偶数尺寸，显示器为奇数尺寸时裁掉最后一行/列
+        log::warn!("显示器尺寸 {}x{} 含奇数边，裁剪为偶数以满足 yuv420p", width, height);
+        filters.push("crop=trunc(iw/2)*2:trunc(ih/2)*2".to_string());
+    }
+    filters.push("format=yuv420p".to_string());
+    video = graph.link(&[&video], &filters, "v");
+
+    if let Some(pip) = &config.pip {
+        video = pip.compose(graph, &video, next_input, config.fps);
+        next_input += 1;
+    }
+
+    // 叠加在缩放之后，保证字号不受缩放影响
+    video = graph.link(&[&video], &config.overlay_filters(), "v");
+
+    if source == VideoSource::Device || native_watermark(config).is_none() {
+        if let Some(watermark) = &config.watermark {
+            video = watermark.compose(graph, &video, next_input);
+        }
+    }
+    video
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::audio::AudioSettings;
+    use crate::audio_filter::AudioFilters;
+    use crate::codec::VideoCodec;
+    use crate::config::StreamProtocol;
+    use crate::ffmpeg_encoder::FfmpegCapabilities;
+    use crate::level_meter::SilenceSettings;
+    use crate::orientation::{Fit, Orientation, OutputPreset};
+    use crate::overlay::{OverlayPosition, TextOverlay};
+    use crate::pip::{PictureInPicture, PipShape};
+    use crate::rate_control::RateControl;
+    use crate::tuning::EncoderTuning;
+    use crate::watermark::Watermark;
+
+    fn config() -> RecorderConfig {
+        RecorderConfig {
+            output: "out.mp4".to_string(),
+            device: ":0".to_string(),
+            audio_sources: Vec::new(),
+            audio_layout: Default::default(),
+            audio: AudioSettings::default(),
+            audio_filters: AudioFilters::default(),
+            loudness_target: None,
+            audio_control: false,
+            level_meter: false,
+            silence: SilenceSettings { threshold_db: -50.0, timeout_secs: 0, abort: false },
+            draw_mouse: true,
+            fps: 30,
+            resolution: "1920x1080".to_string(),
+            bitrate: "5000k".parse().unwrap(),
+            codec: VideoCodec::H264,
+            rate_control: RateControl::Vbr { maxrate: None, bufsize: None },
+            tuning: EncoderTuning::default(),
+            duration: 0,
+            is_stream: false,
+            protocol: StreamProtocol::File,
+            crash_safe: false,
+            format: None,
+            low_latency: false,
+            latency_probe: false,
+            overlay: TextOverlay {
+                items: Vec::new(),
+                position: OverlayPosition::TopLeft,
+                font: None,
+                font_size: 24,
+                font_color: "white".to_string(),
+                box_color: None,
+            },
+            masks: Vec::new(),
+            pip: None,
+            watermark: None,
+            orientation: Orientation::default(),
+            audio_only: false,
+            native_capture: false,
+            av_offset: 0.0,
+            ffmpeg: Arc::new(FfmpegCapabilities {
+                path: PathBuf::from("ffmpeg"),
+                version: "6.1".to_string(),
+                configuration: Vec::new(),
+                encoders: Default::default(),
+                muxers: Default::default(),
+                input_devices: Default::default(),
+                filters: Default::default(),
+            }),
+        }
+    }
+
+    #[test]
+    fn labels_are_unique_per_graph() {
+        let mut graph = FilterGraph::default();
+        let input = Pad::video_input(0);
+        let first = graph.link(&[&input], &["null"], "v");
+        let second = graph.link(&[&first], &["null"], "v");
+        let (left, right) = graph.split(&second, "v");
+        let labels = [&first, &second, &left, &right];
+        for (i, a) in labels.iter().enumerate() {
+            for b in &labels[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+        assert_eq!(graph.to_string(), "[0:v]null[v1];[v1]null[v2];[v2]split[v3][v4]");
+    }
+
+    #[test]
+    fn link_without_filters_passes_input_through() {
+        let mut graph = FilterGraph::default();
+        let input = Pad::video_input(0);
+        let output = graph.link::<&str>(&[&input], &[], "v");
+        assert_eq!(output, input);
+        assert!(graph.is_empty());
+
+        // 多路输入即使没有滤镜也需要生成链
+        let audio = Pad::audio_input(1);
+        graph.link::<&str>(&[&input, &audio], &[], "v");
+        assert!(!graph.is_empty());
+    }
+
+    #[test]
+    fn map_arg_brackets_labels_only() {
+        assert_eq!(Pad::video_input(0).map_arg(), "0:v");
+        assert_eq!(Pad::audio_input(2).map_arg(), "2:a");
+        assert_eq!(Pad::Label("aout3".to_string()).map_arg(), "[aout3]");
+        assert_eq!(Pad::video_input(0).to_string(), "[0:v]");
+        assert_eq!(Pad::Label("v1".to_string()).to_string(), "[v1]");
+    }
+
+    #[test]
+    fn device_source_scales_to_resolution() {
+        let mut graph = FilterGraph::default();
+        let video = video(&config(), &mut graph, VideoSource::Device);
+        assert_eq!(graph.to_string(), "[0:v]scale=1920:1080:flags=lanczos,format=yuv420p[v1]");
+        assert_eq!(video.map_arg(), "[v1]");
+    }
+
+    #[test]
+    fn pipe_source_crops_odd_monitor_sizes() {
+        let mut graph = FilterGraph::default();
+        video(&config(), &mut graph, VideoSource::Pipe { width: 1365, height: 768 });
+        assert_eq!(graph.to_string(), "[0:v]crop=trunc(iw/2)*2:trunc(ih/2)*2,format=yuv420p[v1]");
+    }
+
+    #[test]
+    fn pipe_source_leaves_watermark_to_native_blend() {
+        let mut config = config();
+        config.watermark = Some(Watermark {
+            path: PathBuf::from("logo.png"),
+            position: OverlayPosition::BottomRight,
+            margin: 16,
+            scale: 1.0,
+            opacity: 1.0,
+        });
+        let pipe = VideoSource::Pipe { width: 1920, height: 1080 };
+
+        // 未调整方向时水印由程序内合成，不出现在滤镜图中
+        let mut graph = FilterGraph::default();
+        video(&config, &mut graph, pipe);
+        assert!(native_watermark(&config).is_some());
+        assert_eq!(graph.to_string(), "[0:v]format=yuv420p[v1]");
+
+        // 调整方向后画面几何改变，水印改为滤镜图最后的 overlay
+        config.orientation.rotation = "90".parse().unwrap();
+        let mut graph = FilterGraph::default();
+        video(&config, &mut graph, pipe);
+        assert!(native_watermark(&config).is_none());
+        assert!(graph.to_string().contains("[1:v]scale=trunc(iw*1):trunc(ih*1),format=rgba[wm"));
+    }
+
+    #[test]
+    fn stages_chain_mask_orientation_pip_watermark() {
+        let mut config = config();
+        config.masks = vec!["0,0,100,50:fill=black".parse().unwrap()];
+        config.orientation = Orientation {
+            preset: Some("square".parse::<OutputPreset>().unwrap()),
+            fit: Fit::Pad,
+            ..Default::default()
+        };
+        config.pip = Some(PictureInPicture {
+            input: "cam.mp4".to_string(),
+            format: None,
+            position: OverlayPosition::BottomRight,
+            width: 320,
+            height: 180,
+            margin: 16,
+            border: 0,
+            border_color: "white".to_string(),
+            shape: PipShape::Rectangle,
+        });
+        config.watermark = Some(Watermark {
+            path: PathBuf::from("logo.png"),
+            position: OverlayPosition::TopRight,
+            margin: 8,
+            scale: 0.5,
+            opacity: 1.0,
+        });
+
+        let mut graph = FilterGraph::default();
+        let video = video(&config, &mut graph, VideoSource::Device);
+        // 遮挡 → 画幅 → 像素格式 → 画中画（第 1 路输入）→ 水印（第 2 路输入），每一阶段消费上一阶段的输出
+        assert_eq!(
+            graph.to_string(),
+            "[0:v]drawbox=x=0:y=0:w=100:h=50:color=0x000000:t=fill[masked1];\
+             [masked1]scale=1080:1080:force_original_aspect_ratio=decrease:flags=lanczos,\
+             pad=1080:1080:x=(ow-iw)/2:y=(oh-ih)/2:color=black,setsar=1[reframed2];\
+             [reframed2]format=yuv420p[v3];\
+             [1:v]scale=320:180:force_original_aspect_ratio=increase,crop=320:180,setsar=1[pip4];\
+             [v3][pip4]overlay=x=W-w-16:y=H-h-16[v5];\
+             [2:v]scale=trunc(iw*0.5):trunc(ih*0.5),format=rgba[wm6];\
+             [v5][wm6]overlay=x=W-w-8:y=8:shortest=1,format=yuv420p[v7]"
+        );
+        assert_eq!(video.map_arg(), "[v7]");
+    }
+}
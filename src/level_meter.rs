@@ -0,0 +1,203 @@
+/// 实时音频电平监测与静音告警
+///
+/// 每个音频源的滤镜链中插入 astats，约每 0.2 秒统计一次整体峰值与 RMS，
+/// 由 ametadata 打印到 FFmpeg 日志；读取 stderr 时解析这些行并更新各音频源的电平。
+/// 录制/推流的监控循环定期调用 [`LevelMeter::poll`]：输出电平摘要，
+/// 音频源持续静音超过设定时间时告警，启用 `--abort-on-silence` 时返回错误以终止会话。
+use log::{info, warn};
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::RecorderConfig;
+use crate::error::RecorderError;
+
+/// 电平监测用到的 FFmpeg 滤镜
+pub const REQUIRED_FILTERS: [&str; 3] = ["asetnsamples", "astats", "ametadata"];
+/// 默认静音阈值（dBFS），RMS 低于该值视为静音
+pub const DEFAULT_SILENCE_THRESHOLD_DB: f64 = -60.0;
+/// 允许的静音阈值范围（dBFS）
+pub const SILENCE_THRESHOLD_RANGE: RangeInclusive<f64> = -100.0..=-10.0;
+
+/// ametadata 实例名前缀，后接音频源序号，用于在日志中区分音频源
+const INSTANCE_PREFIX: &str = "ametadata@level";
+/// 每组统计的采样数（48 kHz 下 0.2 秒）
+const SAMPLES_PER_MEASUREMENT: u32 = 9600;
+/// 电平摘要的输出间隔
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 静音告警设置
+#[derive(Debug, Clone)]
+pub struct SilenceSettings {
+    /// RMS 低于该值（dBFS）视为静音
+    pub threshold_db: f64,
+    /// 持续静音多少秒后告警，0 表示不告警
+    pub timeout_secs: u32,
+    /// 告警时终止录制/推流
+    pub abort: bool,
+}
+
+/// 某个音频源的最新电平（dBFS，静音为负无穷）
+#[derive(Debug, Clone)]
+pub struct SourceLevel {
+    pub label: &'static str,
+    pub peak_db: f64,
+    pub rms_db: f64,
+    /// 最近一次 RMS 高于静音阈值的时刻
+    last_sound: Instant,
+    /// 当前这段静音是否已经告警
+    alerted: bool,
+    /// 被运行时控制主动静音，不做静音告警
+    muted: bool,
+}
+
+impl SourceLevel {
+    pub fn silent_for(&self) -> Duration {
+        self.last_sound.elapsed()
+    }
+}
+
+/// 插入到音频源滤镜链中的电平统计滤镜（不改变音频内容）
+pub fn meter_filter(index: usize) -> String {
+    format!(
+        "asetnsamples=n={}:p=0,astats=metadata=1:reset=1:measure_perchannel=none:measure_overall=Peak_level+RMS_level,{}{}=mode=print",
+        SAMPLES_PER_MEASUREMENT, INSTANCE_PREFIX, index
+    )
+}
+
+pub struct LevelMeter {
+    sources: Mutex<Vec<SourceLevel>>,
+    silence: SilenceSettings,
+    last_summary: Mutex<Instant>,
+}
+
+impl LevelMeter {
+    /// 配置未启用电平监测时返回 None
+    pub fn new(config: &RecorderConfig) -> Option<Arc<Self>> {
+        if !config.level_metering() {
+            return None;
+        }
+        let now = Instant::now();
+        let sources = config
+            .audio_sources
+            .iter()
+            .map(|s| SourceLevel {
+                label: s.label(),
+                peak_db: f64::NEG_INFINITY,
+                rms_db: f64::NEG_INFINITY,
+                last_sound: now,
+                alerted: false,
+                muted: false,
+            })
+            .collect();
+        Some(Arc::new(Self {
+            sources: Mutex::new(sources),
+            silence: config.silence.clone(),
+            last_summary: Mutex::new(now),
+        }))
+    }
+
+    /// 解析一行 FFmpeg 日志；属于电平输出的行返回 true（调用方不再写入日志）
+    pub fn handle_line(&self, line: &str) -> bool {
+        let Some(rest) = line.trim_start().strip_prefix('[').and_then(|l| l.strip_prefix(INSTANCE_PREFIX)) else {
+            return false;
+        };
+        let Some((index, rest)) = rest.split_once(' ') else {
+            return false;
+        };
+        let Ok(index) = index.parse::<usize>() else {
+            return false;
+        };
+
+        // 每组统计先输出一行 `frame:N pts:...`，随后每个指标一行 `lavfi.astats.Overall.<键>=<值>`
+        let Some((key, value)) = rest
+            .split_once("] lavfi.astats.Overall.")
+            .and_then(|(_, kv)| kv.split_once('='))
+        else {
+            return true;
+        };
+        let Ok(value) = value.trim().parse::<f64>() else {
+            return true;
+        };
+
+        let mut sources = self.sources.lock().expect("电平状态锁");
+        if let Some(source) = sources.get_mut(index) {
+            match key {
+                "Peak_level" => source.peak_db = value,
+                "RMS_level" => {
+                    source.rms_db = value;
+                    if value > self.silence.threshold_db {
+                        if source.alerted {
+                            info!("🔈 {}恢复有声 (RMS {:.1} dB)", source.label, value);
+                        }
+                        source.last_sound = Instant::now();
+                        source.alerted = false;
+                    }
+                }
+                _ => {}
+            }
+        }
+        true
+    }
+
+    /// 运行时控制主动静音/取消静音；取消后重新开始计算静音时长
+    pub fn set_muted(&self, index: usize, muted: bool) {
+        let mut sources = self.sources.lock().expect("电平状态锁");
+        if let Some(source) = sources.get_mut(index) {
+            if source.muted && !muted {
+                source.last_sound = Instant::now();
+                source.alerted = false;
+            }
+            source.muted = muted;
+        }
+    }
+
+    /// 当前各音频源的电平
+    pub fn levels(&self) -> Vec<SourceLevel> {
+        self.sources.lock().expect("电平状态锁").clone()
+    }
+
+    /// 由监控循环定期调用：输出电平摘要并检查静音
+    ///
+    /// 启用终止选项且某个音频源静音超时时返回 `SilenceDetected`。
+    pub fn poll(&self) -> Result<(), RecorderError> {
+        {
+            let mut last_summary = self.last_summary.lock().expect("电平状态锁");
+            if last_summary.elapsed() >= SUMMARY_INTERVAL {
+                *last_summary = Instant::now();
+                let summary: Vec<String> = self
+                    .levels()
+                    .iter()
+                    .map(|s| format!("{} 峰值 {:.1} dB / RMS {:.1} dB", s.label, s.peak_db, s.rms_db))
+                    .collect();
+                info!("🎚️  音频电平: {}", summary.join("，"));
+            }
+        }
+
+        if self.silence.timeout_secs == 0 {
+            return Ok(());
+        }
+        let timeout = Duration::from_secs(self.silence.timeout_secs as u64);
+        let mut sources = self.sources.lock().expect("电平状态锁");
+        for source in sources.iter_mut().filter(|s| !s.alerted && !s.muted) {
+            let silent = source.silent_for();
+            if silent < timeout {
+                continue;
+            }
+            source.alerted = true;
+            if self.silence.abort {
+                return Err(RecorderError::SilenceDetected {
+                    label: source.label.to_string(),
+                    seconds: silent.as_secs(),
+                });
+            }
+            warn!(
+                "🔇 {}已静音 {} 秒（RMS 低于 {} dB），请检查是否被静音或设备是否正确",
+                source.label,
+                silent.as_secs(),
+                self.silence.threshold_db
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+/// 录制结束后的 EBU R128 响度标准化（loudnorm 双遍处理）
+///
+/// 第一遍测量每条音轨的响度，第二遍用测量值做线性标准化，避免单遍动态模式带来的音量起伏。
+/// 视频与字幕流直接复制，只重新编码音频；处理失败时保留原始录制文件。
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use serde::Deserialize;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use crate::config::{RecorderConfig, StreamProtocol};
+
+/// EBU R128 推荐的节目响度（LUFS）
+pub const DEFAULT_TARGET_LUFS: f64 = -23.0;
+/// 允许的目标响度范围（LUFS），与 loudnorm 的 I 参数一致
+pub const TARGET_RANGE: RangeInclusive<f64> = -70.0..=-5.0;
+
+/// 真峰值上限（dBTP）
+const TRUE_PEAK: f64 = -1.0;
+/// 响度范围（LU）
+const LOUDNESS_RANGE: f64 = 11.0;
+
+/// loudnorm 第一遍以 JSON 输出的测量结果（数值均为字符串）
+#[derive(Debug, Deserialize)]
+struct Measurement {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+    input_thresh: String,
+    target_offset: String,
+}
+
+impl Measurement {
+    /// 静音音轨的测量值为 `-inf`，无法用于第二遍
+    fn is_usable(&self) -> bool {
+        [&self.input_i, &self.input_tp, &self.input_lra, &self.input_thresh, &self.target_offset]
+            .iter()
+            .all(|v| v.parse::<f64>().is_ok_and(f64::is_finite))
+    }
+}
+
+/// 若启用了响度标准化，处理已写完的输出文件
+pub fn normalize(config: &RecorderConfig) -> Result<()> {
+    let Some(target) = config.loudness_target else {
+        return Ok(());
+    };
+    if config.protocol != StreamProtocol::File || !config.has_audio() {
+        return Ok(());
+    }
+
+    let output = Path::new(&config.output);
+    if !output.exists() {
+        bail!("未找到录制文件，无法进行响度标准化: {}", output.display());
+    }
+
+    info!("🔊 响度标准化 (EBU R128, 目标 {} LUFS): {}", target, output.display());
+    let mut measurements = Vec::new();
+    for track in 0..config.audio_track_count() {
+        let m = measure(config, output, track, target)?;
+        if !m.is_usable() {
+            warn!("第 {} 条音轨为静音，跳过响度标准化", track + 1);
+            return Ok(());
+        }
+        info!("📏 音轨 {}: {} LUFS, 真峰值 {} dBTP", track + 1, m.input_i, m.input_tp);
+        measurements.push(m);
+    }
+
+    let temp = temp_path(output);
+    if let Err(e) = apply(config, output, &temp, target, &measurements) {
+        let _ = std::fs::remove_file(&temp);
+        warn!("响度标准化失败，保留原始录制文件 {}", output.display());
+        return Err(e);
+    }
+    std::fs::rename(&temp, output)
+        .with_context(|| format!("无法用标准化结果替换 {}", output.display()))?;
+
+    info!("✅ 响度标准化完成");
+    Ok(())
+}
+
+fn loudnorm_target(target: f64) -> String {
+    format!("loudnorm=I={}:TP={}:LRA={}", target, TRUE_PEAK, LOUDNESS_RANGE)
+}
+
+/// 第一遍：测量单条音轨
+fn measure(config: &RecorderConfig, input: &Path, track: usize, target: f64) -> Result<Measurement> {
+    let out = config
+        .ffmpeg
+        .command()
+        .arg("-hide_banner")
+        .arg("-nostats")
+        .arg("-i").arg(input)
+        .arg("-map").arg(format!("0:a:{}", track))
+        .arg("-af").arg(format!("{}:print_format=json", loudnorm_target(target)))
+        .arg("-f").arg("null")
+        .arg("-")
+        .stdin(Stdio::null())
+        .output()
+        .context("无法启动 FFmpeg 测量响度")?;
+
+    if !out.status.success() {
+        bail!("FFmpeg 响度测量失败 ({})", out.status);
+    }
+
+    // JSON 位于 stderr 末尾的 `[Parsed_loudnorm_0 @ ...]` 之后
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let json = stderr
+        .rfind('{')
+        .and_then(|start| stderr[start..].find('}').map(|end| &stderr[start..=start + end]))
+        .context("FFmpeg 输出中没有 loudnorm 测量结果")?;
+    serde_json::from_str(json).context("无法解析 loudnorm 测量结果")
+}
+
+/// 第二遍：按测量值线性标准化全部音轨，写入临时文件
+fn apply(
+    config: &RecorderConfig,
+    input: &Path,
+    output: &Path,
+    target: f64,
+    measurements: &[Measurement],
+) -> Result<()> {
+    let mut cmd = config.ffmpeg.command();
+    cmd.arg("-hide_banner")
+        .arg("-i").arg(input)
+        .arg("-map").arg("0")
+        .arg("-c").arg("copy");
+
+    for (track, m) in measurements.iter().enumerate() {
+        cmd.arg(format!("-filter:a:{}", track)).arg(format!(
+            "{}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true",
+            loudnorm_target(target),
+            m.input_i,
+            m.input_tp,
+            m.input_lra,
+            m.input_thresh,
+            m.target_offset
+        ));
+    }
+    // loudnorm 内部以 192 kHz 处理，编码参数中的 -ar 将其还原为设定的采样率
+    cmd.args(config.audio_encoder_args());
+    // 未识别的扩展名由 FFmpeg 按临时文件的扩展名推断封装
+    if let Some(container) = config.container() {
+        cmd.args(container.output_args());
+    }
+
+    let out = cmd
+        .arg("-y")
+        .arg(output)
+        .stdin(Stdio::null())
+        .output()
+        .context("无法启动 FFmpeg 进行响度标准化")?;
+
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        let tail: Vec<&str> = stderr.lines().rev().take(5).collect();
+        bail!(
+            "FFmpeg 响度标准化失败 ({}): {}",
+            out.status,
+            tail.into_iter().rev().collect::<Vec<_>>().join(" | ")
+        );
+    }
+    Ok(())
+}
+
+/// 与输出同目录的临时文件，保证最终重命名不跨文件系统；保留原扩展名以便 FFmpeg 推断封装
+fn temp_path(output: &Path) -> PathBuf {
+    match output.extension() {
+        Some(ext) => output.with_extension(format!("loudnorm.{}", ext.to_string_lossy())),
+        None => PathBuf::from(format!("{}.loudnorm", output.display())),
+    }
+}
@@ -4,20 +4,59 @@ mod stream;
 mod config;
 mod error;
 mod native_capture;
+mod recovery;
+mod container;
+mod codec;
+mod rate_control;
+mod tuning;
+mod bitrate;
+mod constraints;
+mod audio;
+mod validation;
+mod audio_filter;
+mod loudness;
+mod level_meter;
+mod audio_control;
+mod overlay;
+mod watermark;
+mod privacy_mask;
+mod pip;
+mod filter_graph;
+mod orientation;
 
 use anyhow::Result;
-use clap::Parser;
-use log::info;
+use clap::{Parser, Subcommand};
+use log::{info, warn};
+use std::sync::Arc;
 
+use crate::audio::{AudioCodec, AudioLayout, AudioSettings, AudioSource, AudioSourceKind};
+use crate::audio_filter::{AudioFilters, Denoise};
+use crate::bitrate::Bitrate;
+use crate::codec::VideoCodec;
 use crate::config::{RecorderConfig, StreamProtocol};
+use crate::error::RecorderError;
+use crate::level_meter::SilenceSettings;
+use crate::overlay::{OverlayItem, OverlayPosition, TextOverlay};
+use crate::orientation::{CropRegion, Fit, Flip, Orientation, OutputPreset, Rotation};
+use crate::pip::{PictureInPicture, PipShape};
+use crate::privacy_mask::PrivacyMask;
+use crate::container::Container;
+use crate::rate_control::RateControl;
+use crate::tuning::{EncoderTuning, Tune};
+use crate::validation::{ReportFormat, ValidationReport};
+use crate::watermark::Watermark;
 
 #[derive(Parser, Debug)]
 #[command(name = "Screen Recorder")]
 #[command(about = "FFmpeg-based screen recorder and streaming tool", long_about = None)]
+#[command(subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
     /// 输出文件路径或 RTMP 流地址
-    #[arg(short, long)]
-    output: String,
+    #[arg(short, long, required = true)]
+    output: Option<String>,
 
     /// 屏幕捕获设备 (Windows: desktop, Linux: :0)
     #[arg(short = 'd', long, default_value = "desktop")]
@@ -35,7 +74,244 @@ struct Args {
     #[arg(short = 'b', long, default_value = "5000k")]
     bitrate: String,
 
-    /// 编码器 (h264, h265, libx264, libx265)
+    /// 码率控制模式 (crf, cqp, vbr, cbr)，默认录制 vbr、推流 cbr
+    #[arg(long)]
+    rate_control: Option<String>,
+
+    /// crf/cqp 模式的质量值（越小画质越高，默认取编码器推荐值）
+    #[arg(long)]
+    quality: Option<u32>,
+
+    /// 峰值码率（vbr 模式，格式同 --bitrate）
+    #[arg(long)]
+    maxrate: Option<String>,
+
+    /// 码率控制缓冲区大小（vbr/cbr 模式，默认为 2 倍码率）
+    #[arg(long)]
+    bufsize: Option<String>,
+
+    /// 编码速度预设（x264/x265: ultrafast..veryslow，nvenc: p1..p7，vp9/av1: 数字档位）
+    #[arg(long)]
+    preset: Option<String>,
+
+    /// 编码调优 (zerolatency, stillimage, film, animation, grain, fastdecode)
+    #[arg(long)]
+    tune: Option<String>,
+
+    /// 关键帧间隔（秒），推流默认 2 秒
+    #[arg(long)]
+    gop: Option<f64>,
+
+    /// 编码 profile（h264: baseline/main/high）
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// 编码 level（例如 4.1）
+    #[arg(long)]
+    level: Option<String>,
+
+    /// B 帧数量
+    #[arg(long)]
+    bframes: Option<u32>,
+
+    /// 编码线程数（0 表示自动）
+    #[arg(long)]
+    threads: Option<u32>,
+
+    /// 低延迟模式（零延迟调优、无 B 帧、短 GOP、小缓冲），适合远程协助
+    #[arg(long, default_value_t = false)]
+    low_latency: bool,
+
+    /// 在画面上叠加毫秒级本地时间，用于测量端到端延迟
+    #[arg(long, default_value_t = false)]
+    latency_probe: bool,
+
+    /// 叠加本地时间（年-月-日 时:分:秒），用于监控类录制/推流
+    #[arg(long, default_value_t = false)]
+    overlay_timestamp: bool,
+
+    /// 叠加本机主机名
+    #[arg(long, default_value_t = false)]
+    overlay_hostname: bool,
+
+    /// 叠加自定义文字（可重复指定，每项一行）
+    #[arg(long)]
+    overlay_text: Vec<String>,
+
+    /// 叠加帧序号
+    #[arg(long, default_value_t = false)]
+    overlay_frame_counter: bool,
+
+    /// 叠加文字位置 (top-left, top-right, bottom-left, bottom-right)
+    #[arg(long, default_value = "top-left")]
+    overlay_position: String,
+
+    /// 叠加文字字体：字体文件路径或字体名称（中文需使用支持中文的字体）
+    #[arg(long)]
+    overlay_font: Option<String>,
+
+    /// 叠加文字字号（像素）
+    #[arg(long, default_value_t = 24)]
+    overlay_size: u32,
+
+    /// 叠加文字颜色（FFmpeg 颜色写法，例如 white、yellow@0.8、#00FF00）
+    #[arg(long, default_value = "white")]
+    overlay_color: String,
+
+    /// 叠加文字背景框颜色
+    #[arg(long, default_value = "black@0.5")]
+    overlay_box_color: String,
+
+    /// 不绘制叠加文字的背景框
+    #[arg(long, default_value_t = false)]
+    no_overlay_box: bool,
+
+    /// 隐私遮挡区域 X,Y,W,H[:blur|pixelate|fill[=颜色]]（相对采集区域，可重复指定）
+    #[arg(long = "mask")]
+    masks: Vec<String>,
+
+    /// 画中画输入：摄像头设备（需配合 --pip-format）、视频文件或网络流地址
+    #[arg(long)]
+    pip: Option<String>,
+
+    /// 画中画输入格式（Linux 摄像头: v4l2，Windows 摄像头: dshow），文件与网络流可省略
+    #[arg(long)]
+    pip_format: Option<String>,
+
+    /// 画中画尺寸（WIDTHxHEIGHT，输出分辨率下的像素）
+    #[arg(long, default_value = "480x270")]
+    pip_size: String,
+
+    /// 画中画位置 (top-left, top-right, bottom-left, bottom-right)
+    #[arg(long, default_value = "bottom-right")]
+    pip_position: String,
+
+    /// 画中画到画面边缘的距离（像素）
+    #[arg(long, default_value_t = 24)]
+    pip_margin: u32,
+
+    /// 画中画边框宽度（像素，0 表示无边框）
+    #[arg(long, default_value_t = 0)]
+    pip_border: u32,
+
+    /// 画中画边框颜色
+    #[arg(long, default_value = "white")]
+    pip_border_color: String,
+
+    /// 画中画形状 (rect, rounded, circle)
+    #[arg(long, default_value = "rect")]
+    pip_shape: String,
+
+    /// 水印图片（PNG，支持透明通道）
+    #[arg(long)]
+    watermark: Option<std::path::PathBuf>,
+
+    /// 水印位置 (top-left, top-right, bottom-left, bottom-right)
+    #[arg(long, default_value = "bottom-right")]
+    watermark_position: String,
+
+    /// 水印到画面边缘的距离（像素）
+    #[arg(long, default_value_t = 16)]
+    watermark_margin: u32,
+
+    /// 水印缩放倍数（相对图片原始尺寸）
+    #[arg(long, default_value_t = 1.0)]
+    watermark_scale: f64,
+
+    /// 水印不透明度（0-1）
+    #[arg(long, default_value_t = 1.0)]
+    watermark_opacity: f64,
+
+    /// 输出画幅预设 (vertical, portrait, square, landscape)，可带尺寸，如 vertical-1080x1920
+    #[arg(long)]
+    orientation: Option<String>,
+
+    /// 宽高比与画幅预设不一致时的适配方式 (crop, pad, blur)
+    #[arg(long, default_value = "blur")]
+    fit: String,
+
+    /// 先从采集画面中裁出的区域 X,Y,W,H（相对采集区域）
+    #[arg(long)]
+    crop_region: Option<String>,
+
+    /// 顺时针旋转角度 (0, 90, 180, 270)
+    #[arg(long, default_value = "0")]
+    rotate: String,
+
+    /// 翻转画面 (h, v, hv)
+    #[arg(long)]
+    flip: Option<String>,
+
+    /// 纯音频模式：不采集屏幕，只录制或推流 --audio / --system-audio 指定的音频源
+    #[arg(long, default_value_t = false)]
+    audio_only: bool,
+
+    /// 麦克风高通滤波截止频率（Hz），去除低频隆隆声，人声常用 80
+    #[arg(long)]
+    highpass: Option<u32>,
+
+    /// 麦克风降噪 (afftdn, arnndn)
+    #[arg(long)]
+    denoise: Option<String>,
+
+    /// arnndn 降噪使用的 RNNoise 模型文件（.rnnn）
+    #[arg(long)]
+    rnnoise_model: Option<std::path::PathBuf>,
+
+    /// 对麦克风做动态范围压缩，拉近忽大忽小的音量
+    #[arg(long, default_value_t = false)]
+    compressor: bool,
+
+    /// 麦克风增益（dB）
+    #[arg(long, allow_negative_numbers = true)]
+    gain: Option<f64>,
+
+    /// 录制结束后按 EBU R128 做双遍响度标准化（仅文件输出，会重新编码音频）
+    #[arg(long, default_value_t = false)]
+    loudnorm: bool,
+
+    /// 响度标准化的目标响度（LUFS）
+    #[arg(long, default_value_t = loudness::DEFAULT_TARGET_LUFS, allow_negative_numbers = true)]
+    loudness_target: f64,
+
+    /// 运行时音频控制：录制/推流过程中在终端输入 m 切换麦克风静音、v mic 0.5 调整音量等，
+    /// Unix 上也可发送 SIGUSR1 切换麦克风静音（原生捕获不支持，需配合 --use-gdigrab）
+    #[arg(long, default_value_t = false)]
+    audio_control: bool,
+
+    /// 关闭实时音频电平监测（默认在启用音频时每 10 秒输出各音频源的峰值与 RMS）
+    #[arg(long, default_value_t = false)]
+    no_level_meter: bool,
+
+    /// 静音阈值（dBFS），RMS 低于该值视为静音
+    #[arg(long, default_value_t = level_meter::DEFAULT_SILENCE_THRESHOLD_DB, allow_negative_numbers = true)]
+    silence_threshold: f64,
+
+    /// 音频源持续静音多少秒后告警（0 表示不告警）
+    #[arg(long, default_value_t = 30)]
+    silence_timeout: u32,
+
+    /// 静音告警时终止录制/推流（已录制的内容会正常保存）
+    #[arg(long, default_value_t = false)]
+    abort_on_silence: bool,
+
+    /// 音频相对视频的偏移（秒），正值使音频延后、负值使音频提前，用于手动校正音画不同步
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    av_offset: f64,
+
+    /// 严格模式：编码约束不满足时列出全部问题并退出，而不是自动修正
+    #[arg(long, default_value_t = false)]
+    strict: bool,
+
+    /// 码率明显偏离分辨率与帧率的推荐范围时自动调整到范围边界（默认只提示）
+    #[arg(long, default_value_t = false)]
+    fix_bitrate: bool,
+
+    /// 配置校验报告格式 (text, json)，json 输出到标准输出供脚本解析
+    #[arg(long, default_value = "text")]
+    report_format: ReportFormat,
+
+    /// 编码器 (h264, h265, nvenc, hevc_nvenc, qsv, vp9, av1, libaom-av1)
     #[arg(short = 'c', long, default_value = "h264")]
     codec: String,
 
@@ -47,10 +323,42 @@ struct Args {
     #[arg(long, default_value = "false")]
     stream: bool,
 
-    /// 是否启用音频采集
+    /// 是否启用音频采集（麦克风）
     #[arg(long, default_value_t = false)]
     audio: bool,
 
+    /// 采集系统播放的声音（Linux: PulseAudio 默认输出设备的 monitor），可与 --audio 同时使用
+    #[arg(long, default_value_t = false)]
+    system_audio: bool,
+
+    /// 麦克风音量倍数（1.0 为原始音量）
+    #[arg(long, default_value = "1.0")]
+    mic_volume: f64,
+
+    /// 系统声音音量倍数（1.0 为原始音量）
+    #[arg(long, default_value = "1.0")]
+    system_volume: f64,
+
+    /// 麦克风与系统声音分别写入独立音轨，而不是混音为一条
+    #[arg(long, default_value_t = false)]
+    separate_audio_tracks: bool,
+
+    /// 麦克风音轨标题（默认“麦克风”）
+    #[arg(long)]
+    mic_title: Option<String>,
+
+    /// 系统声音音轨标题（默认“系统声音”）
+    #[arg(long)]
+    system_title: Option<String>,
+
+    /// 麦克风音轨语言（ISO 639-2，例如 chi、eng）
+    #[arg(long)]
+    mic_language: Option<String>,
+
+    /// 系统声音音轨语言（ISO 639-2，例如 chi、eng）
+    #[arg(long)]
+    system_language: Option<String>,
+
     /// 禁用鼠标指针显示（使用 Desktop Duplication API 高性能模式）。
     /// 默认：显示鼠标（使用 gdigrab）
     #[arg(long, default_value_t = false)]
@@ -60,15 +368,62 @@ struct Args {
     #[arg(long)]
     audio_device: Option<String>,
 
+    /// 音频编码器 (aac, opus, mp3, flac, pcm)，默认按容器选择（webm 为 opus，其余为 aac）
+    #[arg(long)]
+    audio_codec: Option<String>,
+
+    /// 音频码率（有损编码，格式同 --bitrate）
+    #[arg(long, default_value = "128k")]
+    audio_bitrate: String,
+
+    /// 音频采样率 (44100, 48000)，默认 44100（opus 为 48000）
+    #[arg(long)]
+    sample_rate: Option<u32>,
+
+    /// 音频声道数（1 = 单声道，2 = 立体声）
+    #[arg(long, default_value = "2")]
+    channels: u32,
+
     /// 使用 gdigrab 而不是 Desktop Duplication API（仅 Windows）
     #[arg(long, default_value_t = false)]
     use_gdigrab: bool,
 
+    /// 输出容器格式 (mp4, mkv, mov, webm, ts, flv)，默认根据输出扩展名推断
+    #[arg(long)]
+    format: Option<String>,
+
+    /// 崩溃安全录制：先写入分片 MP4，正常结束后重封装（中断后可用 recover 恢复）
+    #[arg(long, default_value_t = false)]
+    crash_safe: bool,
+
+    /// FFmpeg 可执行文件路径（默认依次查找 FFMPEG_PATH 环境变量、程序目录与 PATH）
+    #[arg(long, global = true)]
+    ffmpeg_path: Option<String>,
+
     /// 日志级别 (trace, debug, info, warn, error)
-    #[arg(long, default_value = "info")]
+    #[arg(long, default_value = "info", global = true)]
     log_level: String,
 }
 
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// 列出可用的采集设备
+    Devices {
+        #[command(subcommand)]
+        kind: DeviceKind,
+    },
+
+    /// 将中断的崩溃安全录制文件（*.part）重封装为可播放的 MP4
+    Recover {
+        /// 中断的分片录制文件
+        input: String,
+
+        /// 输出 MP4 路径（默认去掉 .part 后缀）
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
@@ -76,108 +431,318 @@ async fn main() -> Result<()> {
     // 初始化日志
     init_logger(&args.log_level);
 
+    if let Some(command) = &args.command {
+        return run_command(command, &args);
+    }
+
+    // subcommand_negates_reqs 仅在存在子命令时放宽必填，此处一定有值
+    let output = args.output.clone().unwrap_or_default();
+
     info!("🎬 屏幕录制器启动");
-    info!("📹 输出: {}", args.output);
+    info!("📹 输出: {}", output);
     info!("📊 分辨率: {}", args.resolution);
     info!("⏱️  帧率: {} FPS", args.fps);
     info!("🎥 比特率: {}", args.bitrate);
     info!("🔧 编码器: {}", args.codec);
 
+    // 收集全部配置问题后统一报告；解析失败的参数记为 None，依赖它们的检查随之跳过
+    let mut report = ValidationReport::default();
+
+    // 定位 FFmpeg 并探测能力，失败时同样写入报告，保证 --report json 总有输出
+    info!("⚙️  初始化 FFmpeg...");
+    let ffmpeg = match ffmpeg_encoder::init_ffmpeg(args.ffmpeg_path.as_deref()) {
+        Ok(caps) => Some(Arc::new(caps)),
+        Err(e) => {
+            let value = args.ffmpeg_path.as_deref().unwrap_or("(自动查找)");
+            match e.downcast::<RecorderError>() {
+                Ok(err) => report.reject("ffmpeg_path", value, err),
+                Err(e) => report.error("ffmpeg_path", value, format!("{:#}", e)),
+            };
+            None
+        }
+    };
+
+    // 纯音频模式不编码视频：视频参数单独检查，问题降级为警告，不阻止录制
+    let mut video_report = ValidationReport::default();
+    let video_issues = if args.audio_only { &mut video_report } else { &mut report };
+    let mut codec = video_issues.parse::<VideoCodec>("codec", &args.codec);
+    let mut bitrate = video_issues.parse::<Bitrate>("bitrate", &args.bitrate);
+    let maxrate = args.maxrate.as_deref().and_then(|v| video_issues.parse::<Bitrate>("maxrate", v));
+    let bufsize = args.bufsize.as_deref().and_then(|v| video_issues.parse::<Bitrate>("bufsize", v));
+    if args.audio_only {
+        // 这些取值不会用于编码，无效时换成占位值即可
+        codec = codec.or(Some(VideoCodec::H264));
+        bitrate = bitrate.or_else(|| "5000k".parse().ok());
+    }
+    // 码率控制的默认值与检查依赖编码器，编码器无效时不再据此给出提示
+    let rate_control = codec.map(|codec| {
+        RateControl::from_args(
+            args.rate_control.as_deref(),
+            args.quality,
+            maxrate,
+            bufsize,
+            codec,
+            args.stream,
+            video_issues,
+        )
+    });
+    report.merge_as_warnings(video_report, "纯音频模式不编码视频，已忽略");
+    if let (Some(rate_control), false) = (&rate_control, args.audio_only) {
+        info!("🎚️  码率控制: {}", rate_control);
+    }
+
+    let tuning = EncoderTuning {
+        preset: args.preset.clone(),
+        tune: args.tune.as_deref().and_then(|v| report.parse::<Tune>("tune", v)),
+        gop_seconds: args.gop,
+        profile: args.profile.clone(),
+        level: args.level.clone(),
+        bframes: args.bframes,
+        threads: args.threads,
+    };
+    let format = args.format.as_deref().and_then(|v| report.parse::<Container>("format", v));
+
+    let default_audio = AudioSettings::default();
+    let audio = AudioSettings {
+        codec: args.audio_codec.as_deref().and_then(|v| report.parse::<AudioCodec>("audio_codec", v)),
+        bitrate: report.parse("audio_bitrate", &args.audio_bitrate).unwrap_or(default_audio.bitrate),
+        sample_rate: args.sample_rate,
+        channels: args.channels,
+    };
+
+    let audio_filters = AudioFilters {
+        highpass: args.highpass,
+        denoise: args.denoise.as_deref().and_then(|v| report.parse::<Denoise>("denoise", v)),
+        rnnoise_model: args.rnnoise_model.clone(),
+        compressor: args.compressor,
+        gain_db: args.gain,
+    };
+
+    let mut overlay_items = Vec::new();
+    if args.overlay_timestamp {
+        overlay_items.push(OverlayItem::Timestamp);
+    }
+    if args.overlay_hostname {
+        overlay_items.push(OverlayItem::Hostname);
+    }
+    overlay_items.extend(args.overlay_text.iter().cloned().map(OverlayItem::Text));
+    if args.overlay_frame_counter {
+        overlay_items.push(OverlayItem::FrameCounter);
+    }
+    let overlay = TextOverlay {
+        items: overlay_items,
+        position: report
+            .parse::<OverlayPosition>("overlay_position", &args.overlay_position)
+            .unwrap_or_default(),
+        font: args.overlay_font.clone(),
+        font_size: args.overlay_size,
+        font_color: args.overlay_color.clone(),
+        box_color: (!args.no_overlay_box).then(|| args.overlay_box_color.clone()),
+    };
+
+    let masks = args
+        .masks
+        .iter()
+        .filter_map(|m| report.parse::<PrivacyMask>("mask", m))
+        .collect();
+
+    let pip = args.pip.clone().map(|input| {
+        let (width, height) = pip::parse_size(&args.pip_size).unwrap_or_else(|| {
+            report.reject("pip_size", &args.pip_size, RecorderError::InvalidResolution(args.pip_size.clone()));
+            (480, 270)
+        });
+        PictureInPicture {
+            input,
+            format: args.pip_format.clone(),
+            position: report
+                .parse::<OverlayPosition>("pip_position", &args.pip_position)
+                .unwrap_or(OverlayPosition::BottomRight),
+            width,
+            height,
+            margin: args.pip_margin,
+            border: args.pip_border,
+            border_color: args.pip_border_color.clone(),
+            shape: report.parse::<PipShape>("pip_shape", &args.pip_shape).unwrap_or_default(),
+        }
+    });
+
+    let watermark = args.watermark.clone().map(|path| Watermark {
+        path,
+        position: report
+            .parse::<OverlayPosition>("watermark_position", &args.watermark_position)
+            .unwrap_or(OverlayPosition::BottomRight),
+        margin: args.watermark_margin,
+        scale: args.watermark_scale,
+        opacity: args.watermark_opacity,
+    });
+
+    let orientation = Orientation {
+        preset: args.orientation.as_deref().and_then(|p| report.parse::<OutputPreset>("orientation", p)),
+        fit: report.parse::<Fit>("fit", &args.fit).unwrap_or_default(),
+        region: args.crop_region.as_deref().and_then(|r| report.parse::<CropRegion>("crop_region", r)),
+        rotation: report.parse::<Rotation>("rotate", &args.rotate).unwrap_or_default(),
+        flip: args.flip.as_deref().and_then(|f| report.parse::<Flip>("flip", f)),
+    };
+
+    let mut audio_sources = Vec::new();
+    if args.audio {
+        audio_sources.push(AudioSource {
+            kind: AudioSourceKind::Microphone(args.audio_device.clone()),
+            volume: args.mic_volume,
+            title: args.mic_title.clone(),
+            language: args.mic_language.clone(),
+        });
+    }
+    if args.system_audio {
+        audio_sources.push(AudioSource {
+            kind: AudioSourceKind::System,
+            volume: args.system_volume,
+            title: args.system_title.clone(),
+            language: args.system_language.clone(),
+        });
+    }
+
+    // FFmpeg 或关键参数不可用时，约束与兼容性检查都无从谈起，直接输出已收集的问题
+    let (Some(ffmpeg), Some(codec), Some(bitrate), Some(rate_control)) = (ffmpeg, codec, bitrate, rate_control)
+    else {
+        return report.finish(args.report_format).map_err(Into::into);
+    };
+
     // 创建配置 (mutable 用于自动检测协议)
     let mut config = RecorderConfig {
-        output: args.output.clone(),
+        output: output.clone(),
         device: args.device.clone(),
-        audio_enabled: args.audio,
-        audio_device: args.audio_device.clone(),
+        audio_sources,
+        audio_layout: if args.separate_audio_tracks { AudioLayout::Separate } else { AudioLayout::Mix },
+        audio,
+        audio_filters,
+        loudness_target: args.loudnorm.then_some(args.loudness_target),
+        audio_control: args.audio_control,
+        level_meter: !args.no_level_meter,
+        silence: SilenceSettings {
+            threshold_db: args.silence_threshold,
+            timeout_secs: args.silence_timeout,
+            abort: args.abort_on_silence,
+        },
         draw_mouse: !args.no_mouse,  // 反转 no_mouse 标志
         fps: args.fps,
         resolution: args.resolution.clone(),
-        bitrate: args.bitrate.clone(),
-        codec: args.codec.clone(),
+        bitrate,
+        codec,
+        rate_control,
+        tuning,
         duration: args.duration,
         is_stream: args.stream,
         protocol: StreamProtocol::File,
+        crash_safe: args.crash_safe,
+        format,
+        low_latency: args.low_latency,
+        latency_probe: args.latency_probe,
+        overlay,
+        masks,
+        pip,
+        watermark,
+        orientation,
+        audio_only: args.audio_only,
+        native_capture: !args.audio_only && use_native_capture(&args),
+        av_offset: args.av_offset,
+        ffmpeg,
     };
 
     // 根据输出自动检测协议 (RTMP / RTSP / File)
     config.detect_protocol();
 
-    // 验证配置
-    config.validate()?;
+    if config.low_latency {
+        info!("⚡ 低延迟模式");
+        config.apply_low_latency();
+    }
 
-    // 初始化 FFmpeg
-    info!("⚙️  初始化 FFmpeg...");
-    ffmpeg_encoder::init_ffmpeg()?;
+    // 检查编码约束（偶数尺寸、level 上限、码率偏离），默认自动修正尺寸与 level，码率只提示
+    constraints::enforce(&mut config, args.strict, args.fix_bitrate, &mut report);
+
+    // 验证配置（包括已安装 FFmpeg 是否支持所选编码器与封装），一次性列出全部问题
+    config.validate(&mut report);
+    report.finish(args.report_format)?;
 
     // 根据模式选择操作
-    if args.stream {
-        info!("🌐 推流模式: {}", args.output);
-        
-        // 优先使用原生捕获（Desktop Duplication API），但需要鼠标时使用 gdigrab
-        #[cfg(target_os = "windows")]
-        {
-            // 如果需要显示鼠标，使用 gdigrab（支持鼠标绘制）
-            if !args.no_mouse && !args.use_gdigrab {
-                info!("🖱️  需要显示鼠标，使用 gdigrab（包含鼠标指针）");
-                stream::start_streaming(config).await?;
-            } else if !args.use_gdigrab && native_capture::is_desktop_duplication_available() {
-                info!("✨ 使用 Desktop Duplication API（高性能，无鼠标闪烁，但不显示鼠标）");
-                native_capture::start_native_capture_streaming(config).await?;
+    if config.audio_only {
+        info!("🎙️ 纯音频模式: {}", output);
+    } else if args.stream {
+        info!("🌐 推流模式: {}", output);
+    } else {
+        info!("💾 录制模式: {}", output);
+    }
+
+    if config.native_capture {
+        native_capture::start_native_capture_streaming(config).await?;
+    } else if args.stream {
+        stream::start_streaming(config).await?;
+    } else {
+        screen_capture::start_recording(config).await?;
+    }
+
+    info!("✅ 完成");
+    Ok(())
+}
+
+#[derive(Subcommand, Debug)]
+enum DeviceKind {
+    /// 音频输入设备（Linux: PulseAudio/PipeWire 的 source 与 monitor）
+    Audio,
+}
+
+/// 选择屏幕采集方式：优先原生捕获，需要鼠标指针或指定 --use-gdigrab 时使用 FFmpeg 设备采集
+fn use_native_capture(args: &Args) -> bool {
+    // 优先使用原生捕获（Desktop Duplication API），但需要鼠标时使用 gdigrab
+    #[cfg(target_os = "windows")]
+    {
+        // 如果需要显示鼠标，使用 gdigrab（支持鼠标绘制）
+        if !args.no_mouse && !args.use_gdigrab {
+            info!("🖱️  需要显示鼠标，使用 gdigrab（包含鼠标指针）");
+            false
+        } else if !args.use_gdigrab && native_capture::is_desktop_duplication_available() {
+            info!("✨ 使用 Desktop Duplication API（高性能，无鼠标闪烁，但不显示鼠标）");
+            true
+        } else {
+            if args.use_gdigrab {
+                info!("⚠️  使用 gdigrab 模式");
             } else {
-                if args.use_gdigrab {
-                    info!("⚠️  使用 gdigrab 模式");
-                } else {
-                    info!("⚠️  Desktop Duplication API 不可用，回退到 gdigrab");
-                }
-                stream::start_streaming(config).await?;
+                info!("⚠️  Desktop Duplication API 不可用，回退到 gdigrab");
             }
+            false
         }
-        
-        #[cfg(not(target_os = "windows"))]
-        {
-            // 非 Windows 平台尝试使用原生捕获
-            if !args.use_gdigrab && native_capture::is_desktop_duplication_available() {
-                info!("✨ 使用原生屏幕捕获");
-                native_capture::start_native_capture_streaming(config).await?;
-            } else {
-                stream::start_streaming(config).await?;
-            }
+    }
+
+    // 非 Windows 平台尝试使用原生捕获
+    #[cfg(not(target_os = "windows"))]
+    {
+        let native = !args.use_gdigrab && native_capture::is_desktop_duplication_available();
+        if native {
+            info!("✨ 使用原生屏幕捕获");
         }
-    } else {
-        info!("💾 录制模式: {}", args.output);
-        
-        // 录制模式也可以使用原生捕获
-        #[cfg(target_os = "windows")]
-        {
-            // 如果需要显示鼠标，使用 gdigrab
-            if !args.no_mouse && !args.use_gdigrab {
-                info!("🖱️  需要显示鼠标，使用 gdigrab（包含鼠标指针）");
-                screen_capture::start_recording(config).await?;
-            } else if !args.use_gdigrab && native_capture::is_desktop_duplication_available() {
-                info!("✨ 使用 Desktop Duplication API（高性能，无鼠标闪烁，但不显示鼠标）");
-                native_capture::start_native_capture_streaming(config).await?;
-            } else {
-                if args.use_gdigrab {
-                    info!("⚠️  使用 gdigrab 模式");
-                } else {
-                    info!("⚠️  Desktop Duplication API 不可用，回退到 gdigrab");
-                }
-                screen_capture::start_recording(config).await?;
+        native
+    }
+}
+
+fn run_command(command: &Commands, args: &Args) -> Result<()> {
+    let ffmpeg = ffmpeg_encoder::init_ffmpeg(args.ffmpeg_path.as_deref())?;
+    match command {
+        Commands::Devices { kind: DeviceKind::Audio } => {
+            let input_format = audio::input_format();
+            let devices = ffmpeg_encoder::list_audio_devices(&ffmpeg, input_format)?;
+            if devices.is_empty() {
+                warn!("未找到 {} 音频输入设备", input_format);
+                return Ok(());
             }
+            println!("音频输入设备 ({}):", input_format);
+            println!("{}", audio::device_table(&devices));
+            println!();
+            println!("* 为默认输入；--audio-device 使用第一列名称，--system-audio 采集默认输出设备的 monitor");
         }
-        
-        #[cfg(not(target_os = "windows"))]
-        {
-            if !args.use_gdigrab && native_capture::is_desktop_duplication_available() {
-                info!("✨ 使用原生屏幕捕获");
-                native_capture::start_native_capture_streaming(config).await?;
-            } else {
-                screen_capture::start_recording(config).await?;
-            }
+        Commands::Recover { input, output } => {
+            recovery::recover(&ffmpeg, input, output.as_deref())?;
         }
     }
-
-    info!("✅ 完成");
     Ok(())
 }
 
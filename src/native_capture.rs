@@ -3,12 +3,18 @@
 use anyhow::{Result, Context};
 use log::{info, warn, error};
 use scrap::{Capturer, Display};
-use std::io::Write;
-use std::process::{Command, Stdio};
-use std::thread;
+use std::io::{self, Write};
+use std::process::{ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
 
 use crate::config::RecorderConfig;
+use crate::container::Container;
+use crate::level_meter::LevelMeter;
+use crate::filter_graph::{self, FilterGraph, VideoSource};
+use crate::{ffmpeg_encoder, loudness, privacy_mask, recovery};
 
 /// 原生捕获并通过管道传递给 FFmpeg
 pub async fn start_native_capture_streaming(config: RecorderConfig) -> Result<()> {
@@ -19,7 +25,12 @@ pub async fn start_native_capture_streaming(config: RecorderConfig) -> Result<()
     let (width, height) = (display.width(), display.height());
     
     info!("📺 显示器尺寸: {}x{}", width, height);
+    recovery::warn_if_unsupported(&config);
     
+    // 遮挡与水印在本程序内处理每一帧，水印启动时解码一次；调整输出方向时水印由滤镜图合成
+    let watermark = filter_graph::native_watermark(&config).map(|w| w.load()).transpose()?;
+    let mut composed = Vec::new();
+
     // 创建捕获器
     let mut capturer = Capturer::new(display).context("无法创建屏幕捕获器")?;
     
@@ -36,17 +47,25 @@ pub async fn start_native_capture_streaming(config: RecorderConfig) -> Result<()
         .spawn()
         .context("无法启动 FFmpeg 进程")?;
     
-    let mut stdin = child.stdin.take().context("无法获取 FFmpeg stdin")?;
+    let stdin = child.stdin.take().context("无法获取 FFmpeg stdin")?;
+    // 低延迟模式下由写入线程只发送最新一帧，FFmpeg 来不及处理时丢弃积压的旧帧
+    let mut sink = if config.low_latency {
+        FrameSink::Latest(LatestFrameWriter::spawn(stdin))
+    } else {
+        FrameSink::Blocking(stdin)
+    };
     
-    // 在单独线程中处理 FFmpeg stderr
-    if let Some(stderr) = child.stderr.take() {
-        thread::spawn(move || {
-            use std::io::{BufRead, BufReader};
-            let reader = BufReader::new(stderr);
-            for line in reader.lines() {
-                if let Ok(l) = line {
-                    log::info!("ffmpeg | {}", l);
-                }
+    // 在单独线程中处理 FFmpeg stderr，电平统计行交给电平监测
+    let meter = LevelMeter::new(&config);
+    ffmpeg_encoder::log_stderr(&mut child, meter.clone());
+
+    // Ctrl+C 时结束捕获循环，正常关闭 stdin 让 FFmpeg 写完文件
+    let stop_requested = Arc::new(AtomicBool::new(false));
+    {
+        let stop_requested = stop_requested.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                stop_requested.store(true, Ordering::SeqCst);
             }
         });
     }
@@ -65,7 +84,19 @@ pub async fn start_native_capture_streaming(config: RecorderConfig) -> Result<()
     let mut last_frame_time = Instant::now();
     let mut last_success_time = Instant::now();
     
+    // 静音超时终止时先正常结束录制，保留已录制的内容后再返回错误
+    let mut aborted = None;
     loop {
+        if stop_requested.load(Ordering::SeqCst) {
+            info!("⏹️  收到 Ctrl+C，停止捕获");
+            break;
+        }
+        if let Some(Err(e)) = meter.as_ref().map(|m| m.poll()) {
+            error!("🔇 {}", e);
+            aborted = Some(e);
+            break;
+        }
+
         // 检查是否超过时间限制
         if let Some(max_dur) = max_duration {
             if start_time.elapsed() >= max_dur {
@@ -79,8 +110,20 @@ pub async fn start_native_capture_streaming(config: RecorderConfig) -> Result<()
             Ok(frame) => {
                 last_success_time = Instant::now();
                 
-                // 将 BGRA 帧数据写入 FFmpeg stdin
-                if let Err(e) = stdin.write_all(&frame) {
+                // 将 BGRA 帧数据写入 FFmpeg stdin，有遮挡或水印时先在副本上处理
+                let data: &[u8] = if config.masks.is_empty() && watermark.is_none() {
+                    &frame
+                } else {
+                    let stride = frame.len() / height;
+                    composed.clear();
+                    composed.extend_from_slice(&frame);
+                    privacy_mask::apply(&config.masks, &mut composed, width, height, stride);
+                    if let Some(watermark) = &watermark {
+                        watermark.blend(&mut composed, width, height, stride);
+                    }
+                    &composed
+                };
+                if let Err(e) = sink.write(data) {
                     error!("写入 FFmpeg 失败: {}", e);
                     break;
                 }
@@ -88,7 +131,7 @@ pub async fn start_native_capture_streaming(config: RecorderConfig) -> Result<()
                 frame_count += 1;
                 
                 // 每 100 帧输出一次状态
-                if frame_count % 100 == 0 {
+                if frame_count.is_multiple_of(100) {
                     let elapsed = start_time.elapsed().as_secs_f64();
                     let actual_fps = frame_count as f64 / elapsed;
                     info!("📊 已捕获 {} 帧，实际 FPS: {:.2}", frame_count, actual_fps);
@@ -128,7 +171,7 @@ pub async fn start_native_capture_streaming(config: RecorderConfig) -> Result<()
     }
     
     // 关闭 stdin 以通知 FFmpeg 输入结束
-    drop(stdin);
+    sink.close();
     
     info!("⏱️  总共捕获 {} 帧，耗时 {:.2}s", frame_count, start_time.elapsed().as_secs_f64());
     
@@ -137,97 +180,185 @@ pub async fn start_native_capture_streaming(config: RecorderConfig) -> Result<()
     if !status.success() {
         warn!("FFmpeg 进程异常退出: {}", status);
     }
+
+    // 崩溃安全模式：将分片文件重封装为最终 MP4
+    if let Some(partial) = recovery::partial_output(&config) {
+        let container = config.container().unwrap_or(Container::Mp4);
+        recovery::finalize(&config.ffmpeg, &partial, &config.output, container)?;
+    }
+    if let Some(e) = aborted {
+        return Err(e.into());
+    }
+    loudness::normalize(&config)?;
     
     Ok(())
 }
 
+/// 帧的去向：普通模式直接阻塞写入 FFmpeg stdin，低延迟模式经只保留最新一帧的写入线程
+enum FrameSink {
+    Blocking(ChildStdin),
+    Latest(LatestFrameWriter),
+}
+
+impl FrameSink {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        match self {
+            FrameSink::Blocking(stdin) => stdin.write_all(data),
+            FrameSink::Latest(writer) => writer.submit(data),
+        }
+    }
+
+    fn close(self) {
+        if let FrameSink::Latest(writer) = self {
+            let dropped = writer.finish();
+            if dropped > 0 {
+                info!("⚡ 低延迟模式丢弃了 {} 个积压帧", dropped);
+            }
+        }
+    }
+}
+
+/// 写入线程与捕获循环共享的单帧槽位
+#[derive(Default)]
+struct FrameSlot {
+    /// 等待写出的最新一帧
+    pending: Option<Vec<u8>>,
+    /// 已写出的缓冲区，复用以避免每帧分配
+    spare: Option<Vec<u8>>,
+    closed: bool,
+    failed: bool,
+}
+
+/// 只保留最新一帧的写入线程：管道中最多排队一帧，捕获循环不会被编码速度拖住
+struct LatestFrameWriter {
+    shared: Arc<(Mutex<FrameSlot>, Condvar)>,
+    thread: JoinHandle<()>,
+    dropped: u64,
+}
+
+impl LatestFrameWriter {
+    fn spawn(mut stdin: ChildStdin) -> Self {
+        let shared = Arc::new((Mutex::new(FrameSlot::default()), Condvar::new()));
+        let thread = {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                let (lock, ready) = &*shared;
+                loop {
+                    let frame = {
+                        let mut slot = lock.lock().unwrap();
+                        while slot.pending.is_none() && !slot.closed {
+                            slot = ready.wait(slot).unwrap();
+                        }
+                        match slot.pending.take() {
+                            Some(frame) => frame,
+                            None => return,
+                        }
+                    };
+                    let result = stdin.write_all(&frame);
+                    let mut slot = lock.lock().unwrap();
+                    slot.spare = Some(frame);
+                    if let Err(e) = result {
+                        error!("写入 FFmpeg 失败: {}", e);
+                        slot.failed = true;
+                        return;
+                    }
+                }
+            })
+        };
+        LatestFrameWriter { shared, thread, dropped: 0 }
+    }
+
+    /// 放入最新一帧，上一帧尚未写出时直接替换
+    fn submit(&mut self, data: &[u8]) -> io::Result<()> {
+        let (lock, ready) = &*self.shared;
+        let mut slot = lock.lock().unwrap();
+        if slot.failed {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "FFmpeg 已停止接收画面"));
+        }
+        let mut buffer = match slot.pending.take() {
+            Some(stale) => {
+                self.dropped += 1;
+                stale
+            }
+            None => slot.spare.take().unwrap_or_default(),
+        };
+        buffer.clear();
+        buffer.extend_from_slice(data);
+        slot.pending = Some(buffer);
+        ready.notify_one();
+        Ok(())
+    }
+
+    /// 写出最后一帧后关闭 stdin，返回丢弃的帧数
+    fn finish(self) -> u64 {
+        let (lock, ready) = &*self.shared;
+        lock.lock().unwrap().closed = true;
+        ready.notify_one();
+        let _ = self.thread.join();
+        self.dropped
+    }
+}
+
 /// 构建从管道读取的 FFmpeg 命令
 fn build_ffmpeg_pipe_command(config: &RecorderConfig, width: usize, height: usize) -> Result<Command> {
-    let mut cmd = Command::new("ffmpeg");
+    let mut cmd = config.ffmpeg.command();
     
     // 从 stdin 读取原始 BGRA 帧
     cmd.arg("-f").arg("rawvideo");
     cmd.arg("-pix_fmt").arg("bgra");
     cmd.arg("-video_size").arg(format!("{}x{}", width, height));
     cmd.arg("-framerate").arg(config.fps.to_string());
+    // 低延迟模式下输入队列仅保留极少帧，避免管道积压
+    cmd.args(config.low_latency_input_args());
+    // 按帧到达管道的时刻打时间戳，而不是按名义帧率推算，
+    // 否则捕获节奏抖动会在长时间录制中累积为音画不同步
+    cmd.arg("-use_wallclock_as_timestamps").arg("1");
     cmd.arg("-i").arg("pipe:0");
     
-    // 添加音频输入（如果启用）
-    if config.audio_enabled {
-        #[cfg(target_os = "windows")]
-        {
-            let audio_device = config.audio_device.as_deref().unwrap_or("Microphone");
-            cmd.arg("-f").arg("dshow");
-            cmd.arg("-i").arg(format!("audio={}", audio_device));
-        }
-        
-        #[cfg(target_os = "linux")]
-        {
-            let audio_device = config.audio_device.as_deref().unwrap_or("default");
-            cmd.arg("-f").arg("pulse");
-            cmd.arg("-i").arg(audio_device);
-        }
-        
-        #[cfg(target_os = "macos")]
-        {
-            let audio_device = config.audio_device.as_deref().unwrap_or(":0");
-            cmd.arg("-f").arg("avfoundation");
-            cmd.arg("-i").arg(audio_device);
-        }
+    // 添加音频输入（如果启用）：麦克风、系统声音
+    ffmpeg_encoder::push_audio_inputs(&mut cmd, config);
+    // 画中画输入；水印通常在程序内合成，调整了输出方向时才作为 FFmpeg 输入
+    if let Some(pip) = &config.pip {
+        cmd.args(pip.input_args());
+    }
+    if let (Some(watermark), None) = (&config.watermark, filter_graph::native_watermark(config)) {
+        cmd.args(watermark.input_args());
     }
     
     // 视频编码参数
-    let codec = if config.codec == "h264" {
-        "libx264"
-    } else if config.codec == "h265" {
-        "libx265"
-    } else {
-        &config.codec
-    };
-    
-    cmd.arg("-c:v").arg(codec);
-    cmd.arg("-b:v").arg(&config.bitrate);
-    cmd.arg("-preset").arg("veryfast");
-    cmd.arg("-maxrate").arg(&config.bitrate);
-    cmd.arg("-bufsize").arg(&config.bitrate);
+    cmd.arg("-c:v").arg(config.codec.encoder());
+    cmd.args(config.video_encoder_args(true));
     
     // 像素格式转换（BGRA -> YUV420P）
     cmd.arg("-pix_fmt").arg("yuv420p");
-    
-    // 音频编码参数（如果启用）
-    if config.audio_enabled {
-        cmd.arg("-c:a").arg("aac");
-        cmd.arg("-b:a").arg("128k");
-        cmd.arg("-ar").arg("44100");
+    // 按墙钟时间戳补帧/丢帧，输出恒定帧率（-fps_mode 自 FFmpeg 5.1 起可用，旧版本使用 -vsync）
+    if config.ffmpeg.version_at_least(5, 1) {
+        cmd.arg("-fps_mode").arg("cfr");
+    } else {
+        cmd.arg("-vsync").arg("cfr");
     }
+    cmd.arg("-r").arg(config.fps.to_string());
+
+    // 遮挡已在程序内处理，滤镜图处理尺寸与方向、画中画、文字叠加（及调整方向后的水印）
+    let mut graph = FilterGraph::default();
+    let video = filter_graph::video(config, &mut graph, VideoSource::Pipe { width, height });
+    let audio = ffmpeg_encoder::compose_audio(config, &mut graph, 1);
+    ffmpeg_encoder::push_graph(&mut cmd, &graph, Some(&video), &audio);
+
+    // 音轨元数据与音频编码参数（如果启用）
+    ffmpeg_encoder::push_audio_encoder(&mut cmd, config);
     
     // 输出格式和地址
-    match config.protocol {
-        crate::config::StreamProtocol::RTSP => {
-            cmd.arg("-rtsp_transport").arg("tcp");
-            cmd.arg("-f").arg("rtsp");
-        }
-        crate::config::StreamProtocol::RTMP => {
-            cmd.arg("-f").arg("flv");
-            cmd.arg("-flvflags").arg("no_duration_filesize");
-            cmd.arg("-rtmp_live").arg("live");
-        }
-        crate::config::StreamProtocol::File => {
-            cmd.arg("-f").arg("mp4");
-            cmd.arg("-movflags").arg("faststart");
-        }
-    }
-    
-    cmd.arg(&config.output);
-    
-    // 覆盖已存在的文件
-    if config.protocol == crate::config::StreamProtocol::File {
-        cmd.arg("-y");
-    }
+    ffmpeg_encoder::push_output(&mut cmd, config);
     
     Ok(cmd)
 }
 
+/// 主显示器尺寸，即原生捕获送入 FFmpeg 的画面尺寸
+pub fn primary_display_size() -> Option<(u32, u32)> {
+    Display::primary().ok().map(|d| (d.width() as u32, d.height() as u32))
+}
+
 /// 检查系统是否支持 Desktop Duplication API
 pub fn is_desktop_duplication_available() -> bool {
     #[cfg(target_os = "windows")]
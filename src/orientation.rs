@@ -0,0 +1,390 @@
+/// 输出方向与画幅：裁出区域、旋转/翻转，再适配到竖屏等社交平台画幅
+///
+/// 处理顺序为 裁出区域 → 旋转/翻转 → 适配目标尺寸，区域坐标与隐私遮挡一样相对采集区域。
+/// 宽高比不一致时按适配方式处理：crop 居中裁剪，pad 等比缩放后补黑边，
+/// blur 等比缩放后以模糊放大的同一画面作背景。FFmpeg 采集与原生捕获共用同一段滤镜。
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::RecorderError;
+use crate::ffmpeg_encoder::FfmpegCapabilities;
+use crate::filter_graph::{FilterGraph, Pad};
+use crate::validation::ValidationReport;
+
+/// 画幅预设：(名称, 宽高比的宽, 宽高比的高, 省略尺寸时的默认宽, 默认高)
+const PRESETS: &[(&str, u32, u32, u32, u32)] = &[
+    ("vertical", 9, 16, 1080, 1920),
+    ("portrait", 4, 5, 1080, 1350),
+    ("square", 1, 1, 1080, 1080),
+    ("landscape", 16, 9, 1920, 1080),
+];
+
+/// 目标尺寸的最短边（像素）
+const MIN_SIDE: u32 = 128;
+/// 模糊背景先缩小到目标尺寸的 1/8 再模糊并放大，降低逐帧开销
+const BACKGROUND_DOWNSCALE: u32 = 8;
+/// 缩小后背景的盒式模糊半径
+const BACKGROUND_BLUR: u32 = 4;
+
+/// 画幅预设，命令行写法 `vertical` 或 `vertical-1080x1920`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputPreset {
+    name: &'static str,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl fmt::Display for OutputPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}x{}", self.name, self.width, self.height)
+    }
+}
+
+impl FromStr for OutputPreset {
+    type Err = RecorderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        let (name, size) = match lower.split_once('-') {
+            Some((name, size)) => (name, Some(size)),
+            None => (lower.as_str(), None),
+        };
+        let Some(&(name, aw, ah, default_w, default_h)) = PRESETS.iter().find(|p| p.0 == name) else {
+            return Err(RecorderError::InvalidOrientation(format!(
+                "未知的画幅预设 {}，可选 vertical, portrait, square, landscape",
+                s
+            )));
+        };
+        let (width, height) = match size {
+            None => (default_w, default_h),
+            Some(size) => crate::pip::parse_size(size).ok_or_else(|| {
+                RecorderError::InvalidOrientation(format!("画幅预设 {} 的尺寸应为 WIDTHxHEIGHT", s))
+            })?,
+        };
+        // 尺寸必须与预设的宽高比一致，避免 vertical-1920x1080 这类写反的尺寸
+        if width as u64 * ah as u64 != height as u64 * aw as u64 {
+            return Err(RecorderError::InvalidOrientation(format!(
+                "{} 的宽高比应为 {}:{}，例如 {}-{}x{}",
+                s, aw, ah, name, default_w, default_h
+            )));
+        }
+        Ok(OutputPreset { name, width, height })
+    }
+}
+
+/// 宽高比与目标不一致时的适配方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Fit {
+    /// 居中裁剪到目标宽高比
+    Crop,
+    /// 等比缩放后补黑边
+    Pad,
+    /// 等比缩放后以模糊放大的同一画面作背景
+    #[default]
+    Blur,
+}
+
+impl fmt::Display for Fit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Fit::Crop => "crop",
+            Fit::Pad => "pad",
+            Fit::Blur => "blur",
+        })
+    }
+}
+
+impl FromStr for Fit {
+    type Err = RecorderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "crop" => Ok(Fit::Crop),
+            "pad" => Ok(Fit::Pad),
+            "blur" => Ok(Fit::Blur),
+            _ => Err(RecorderError::InvalidOrientation(format!("未知的适配方式 {}，可选 crop, pad, blur", s))),
+        }
+    }
+}
+
+/// 顺时针旋转角度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rotation {
+    #[default]
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+impl Rotation {
+    /// 旋转 90/270 度时宽高互换
+    fn swaps_sides(&self) -> bool {
+        matches!(self, Rotation::Cw90 | Rotation::Cw270)
+    }
+
+    fn filters(&self) -> &'static [&'static str] {
+        match self {
+            Rotation::None => &[],
+            Rotation::Cw90 => &["transpose=clock"],
+            Rotation::Cw180 => &["hflip", "vflip"],
+            Rotation::Cw270 => &["transpose=cclock"],
+        }
+    }
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Rotation::None => "0",
+            Rotation::Cw90 => "90",
+            Rotation::Cw180 => "180",
+            Rotation::Cw270 => "270",
+        })
+    }
+}
+
+impl FromStr for Rotation {
+    type Err = RecorderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "0" | "360" => Ok(Rotation::None),
+            "90" | "-270" => Ok(Rotation::Cw90),
+            "180" | "-180" => Ok(Rotation::Cw180),
+            "270" | "-90" => Ok(Rotation::Cw270),
+            _ => Err(RecorderError::InvalidOrientation(format!("旋转角度 {} 无效，可选 0, 90, 180, 270", s))),
+        }
+    }
+}
+
+/// 翻转方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flip {
+    Horizontal,
+    Vertical,
+    Both,
+}
+
+impl Flip {
+    fn filters(&self) -> &'static [&'static str] {
+        match self {
+            Flip::Horizontal => &["hflip"],
+            Flip::Vertical => &["vflip"],
+            Flip::Both => &["hflip", "vflip"],
+        }
+    }
+}
+
+impl fmt::Display for Flip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Flip::Horizontal => "h",
+            Flip::Vertical => "v",
+            Flip::Both => "hv",
+        })
+    }
+}
+
+impl FromStr for Flip {
+    type Err = RecorderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "h" | "horizontal" => Ok(Flip::Horizontal),
+            "v" | "vertical" => Ok(Flip::Vertical),
+            "hv" | "vh" | "both" => Ok(Flip::Both),
+            _ => Err(RecorderError::InvalidOrientation(format!("未知的翻转方向 {}，可选 h, v, hv", s))),
+        }
+    }
+}
+
+/// 从采集画面中裁出的区域（像素，相对采集区域左上角）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CropRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl fmt::Display for CropRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+/// 命令行写法 `X,Y,W,H`
+impl FromStr for CropRegion {
+    type Err = RecorderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RecorderError::InvalidOrientation(format!("裁剪区域 {} 的格式应为 X,Y,W,H", s));
+        let parts: Vec<u32> = s
+            .split(',')
+            .map(|v| v.trim().parse::<u32>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid())?;
+        let [x, y, width, height] = parts[..] else {
+            return Err(invalid());
+        };
+        if width == 0 || height == 0 {
+            return Err(invalid());
+        }
+        Ok(CropRegion { x, y, width, height })
+    }
+}
+
+/// 输出方向设置，全部为默认值时不做任何处理
+#[derive(Debug, Clone, Default)]
+pub struct Orientation {
+    /// 目标画幅，None 时保持裁出与旋转后的尺寸
+    pub preset: Option<OutputPreset>,
+    pub fit: Fit,
+    pub region: Option<CropRegion>,
+    pub rotation: Rotation,
+    pub flip: Option<Flip>,
+}
+
+impl Orientation {
+    pub fn is_empty(&self) -> bool {
+        self.preset.is_none() && self.region.is_none() && self.rotation == Rotation::None && self.flip.is_none()
+    }
+
+    /// 裁出区域并旋转后的画面尺寸
+    fn oriented_size(&self, capture: (u32, u32)) -> (u32, u32) {
+        let (w, h) = self.region.map_or(capture, |r| (r.width, r.height));
+        if self.rotation.swaps_sides() {
+            (h, w)
+        } else {
+            (w, h)
+        }
+    }
+
+    /// 编码输出的尺寸：预设尺寸，或裁出与旋转后向下取偶的尺寸
+    pub fn output_size(&self, capture: (u32, u32)) -> (u32, u32) {
+        match self.preset {
+            Some(preset) => (preset.width, preset.height),
+            None => {
+                let (w, h) = self.oriented_size(capture);
+                (w & !1, h & !1)
+            }
+        }
+    }
+
+    fn required_filters(&self) -> Vec<&'static str> {
+        let mut filters = Vec::new();
+        if self.region.is_some() {
+            filters.push("crop");
+        }
+        match self.rotation {
+            Rotation::None => {}
+            Rotation::Cw180 => filters.extend(["hflip", "vflip"]),
+            Rotation::Cw90 | Rotation::Cw270 => filters.push("transpose"),
+        }
+        if let Some(flip) = self.flip {
+            filters.extend(flip.filters());
+        }
+        if self.preset.is_some() {
+            filters.extend(match self.fit {
+                Fit::Crop => &["scale", "crop", "setsar"][..],
+                Fit::Pad => &["scale", "pad", "setsar"][..],
+                Fit::Blur => &["split", "scale", "crop", "boxblur", "overlay", "setsar"][..],
+            });
+        }
+        filters.sort_unstable();
+        filters.dedup();
+        filters
+    }
+
+    /// 将方向调整加入滤镜图，返回处理后的端口；`capture` 为已知的采集尺寸
+    ///
+    /// 采集尺寸已知且宽高比恰好与目标一致时直接缩放，不做裁剪或补边。
+    pub fn compose(&self, graph: &mut FilterGraph, input: Pad, capture: Option<(u32, u32)>) -> Pad {
+        let mut filters: Vec<String> = Vec::new();
+        if let Some(r) = self.region {
+            filters.push(format!("crop=w={}:h={}:x={}:y={}", r.width, r.height, r.x, r.y));
+        }
+        let flips = self.flip.iter().flat_map(|f| f.filters());
+        filters.extend(self.rotation.filters().iter().chain(flips).map(|f| f.to_string()));
+
+        let Some(OutputPreset { width: w, height: h, .. }) = self.preset else {
+            // 保持原尺寸，yuv420p 要求偶数尺寸
+            let even = capture.map(|c| self.oriented_size(c)).is_some_and(|(w, h)| w % 2 == 0 && h % 2 == 0);
+            if !even {
+                filters.push("crop=trunc(iw/2)*2:trunc(ih/2)*2".to_string());
+            }
+            return graph.link(&[&input], &filters, "oriented");
+        };
+
+        let same_aspect = capture
+            .map(|c| self.oriented_size(c))
+            .is_some_and(|(sw, sh)| sw as u64 * h as u64 == sh as u64 * w as u64);
+        let fit_inside = format!("scale={}:{}:force_original_aspect_ratio=decrease:flags=lanczos", w, h);
+        match self.fit {
+            _ if same_aspect => filters.push(format!("scale={}:{}:flags=lanczos", w, h)),
+            Fit::Crop => filters.extend([
+                format!("scale={}:{}:force_original_aspect_ratio=increase:flags=lanczos", w, h),
+                format!("crop={}:{}", w, h),
+            ]),
+            Fit::Pad => filters.extend([fit_inside, format!("pad={}:{}:x=(ow-iw)/2:y=(oh-ih)/2:color=black", w, h)]),
+            Fit::Blur => {
+                let oriented = graph.link(&[&input], &filters, "oriented");
+                let (base, fill) = graph.split(&oriented, "reframe");
+                let (bw, bh) = (w / BACKGROUND_DOWNSCALE, h / BACKGROUND_DOWNSCALE);
+                let background = graph.link(
+                    &[&fill],
+                    &[
+                        format!("scale={}:{}:force_original_aspect_ratio=increase", bw, bh),
+                        format!("crop={}:{}", bw, bh),
+                        format!("boxblur={}", BACKGROUND_BLUR),
+                        format!("scale={}:{}", w, h),
+                    ],
+                    "background",
+                );
+                let foreground = graph.link(&[&base], &[fit_inside], "foreground");
+                return graph.link(
+                    &[&background, &foreground],
+                    &["overlay=x=(W-w)/2:y=(H-h)/2", "setsar=1"],
+                    "reframed",
+                );
+            }
+        }
+        filters.push("setsar=1".to_string());
+        graph.link(&[&input], &filters, "reframed")
+    }
+
+    /// `capture_size` 为已知的采集尺寸，用于检查裁剪区域是否落在画面内
+    pub fn validate(&self, ffmpeg: &FfmpegCapabilities, capture_size: Option<(u32, u32)>, report: &mut ValidationReport) {
+        if self.is_empty() {
+            return;
+        }
+        for filter in self.required_filters() {
+            if let Err(e) = ffmpeg.ensure_filter(filter) {
+                report.reject("orientation", filter, e);
+            }
+        }
+        if let Some(preset) = self.preset {
+            if preset.width < MIN_SIDE || preset.height < MIN_SIDE {
+                report
+                    .error("orientation", preset, "画幅尺寸过小")
+                    .suggest(format!("宽高至少 {} 像素", MIN_SIDE));
+            } else if preset.width % 2 != 0 || preset.height % 2 != 0 {
+                report
+                    .error("orientation", preset, "yuv420p 要求宽高均为偶数")
+                    .suggest(format!("改为 {}-{}x{}", preset.name, preset.width & !1, preset.height & !1));
+            }
+        }
+        if let (Some(region), Some((width, height))) = (self.region, capture_size) {
+            if region.x >= width || region.y >= height {
+                report
+                    .error("crop_region", region, format!("区域起点在 {}x{} 的采集区域之外", width, height))
+                    .suggest("坐标相对采集区域左上角，原生捕获时以整个显示器为准");
+            } else if region.x + region.width > width || region.y + region.height > height {
+                report
+                    .error("crop_region", region, format!("区域超出 {}x{} 的采集区域", width, height))
+                    .suggest(format!("宽度不超过 {}，高度不超过 {}", width - region.x, height - region.y));
+            }
+        }
+    }
+}
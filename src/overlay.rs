@@ -0,0 +1,241 @@
+/// 画面文字叠加（drawtext）：本地时间、主机名、自定义文字与帧计数
+///
+/// 每一项为一行，按所选角落依次堆叠；在滤镜图中位于缩放与像素格式转换之后，
+/// 字号与边距不受缩放影响。
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::audio_filter::escape_filter_path;
+use crate::error::RecorderError;
+use crate::ffmpeg_encoder::FfmpegCapabilities;
+use crate::validation::ValidationReport;
+
+/// 允许的字号范围（像素）
+const FONT_SIZE_RANGE: RangeInclusive<u32> = 8..=200;
+/// 文字到画面边缘的距离（像素）
+const MARGIN: u32 = 16;
+/// 背景框内边距（像素）
+const BOX_BORDER: u32 = 6;
+/// Windows 上 drawtext 通常没有 fontconfig，默认使用支持中文的微软雅黑
+const WINDOWS_DEFAULT_FONT: &str = "C:/Windows/Fonts/msyh.ttc";
+
+/// 叠加内容
+#[derive(Debug, Clone, PartialEq)]
+pub enum OverlayItem {
+    /// 本地时间，精确到秒
+    Timestamp,
+    /// 本机主机名（启动时读取）
+    Hostname,
+    Text(String),
+    /// 从 0 开始的帧序号
+    FrameCounter,
+}
+
+/// 叠加文字所在的角落
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverlayPosition {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl OverlayPosition {
+    pub fn is_top(&self) -> bool {
+        matches!(self, OverlayPosition::TopLeft | OverlayPosition::TopRight)
+    }
+
+    pub fn is_left(&self) -> bool {
+        matches!(self, OverlayPosition::TopLeft | OverlayPosition::BottomLeft)
+    }
+
+    fn x(&self) -> String {
+        match self {
+            OverlayPosition::TopLeft | OverlayPosition::BottomLeft => MARGIN.to_string(),
+            OverlayPosition::TopRight | OverlayPosition::BottomRight => format!("w-tw-{}", MARGIN),
+        }
+    }
+}
+
+impl fmt::Display for OverlayPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OverlayPosition::TopLeft => "top-left",
+            OverlayPosition::TopRight => "top-right",
+            OverlayPosition::BottomLeft => "bottom-left",
+            OverlayPosition::BottomRight => "bottom-right",
+        })
+    }
+}
+
+impl FromStr for OverlayPosition {
+    type Err = RecorderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "top-left" | "tl" => Ok(OverlayPosition::TopLeft),
+            "top-right" | "tr" => Ok(OverlayPosition::TopRight),
+            "bottom-left" | "bl" => Ok(OverlayPosition::BottomLeft),
+            "bottom-right" | "br" => Ok(OverlayPosition::BottomRight),
+            _ => Err(RecorderError::InvalidOverlay(format!("未知的位置 {}，可选 top-left, top-right, bottom-left, bottom-right", s))),
+        }
+    }
+}
+
+/// 文字叠加设置，`items` 为空时不叠加
+#[derive(Debug, Clone)]
+pub struct TextOverlay {
+    pub items: Vec<OverlayItem>,
+    pub position: OverlayPosition,
+    /// 字体文件路径，或 fontconfig 字体名称
+    pub font: Option<String>,
+    pub font_size: u32,
+    pub font_color: String,
+    /// 背景框颜色，None 表示不绘制背景框
+    pub box_color: Option<String>,
+}
+
+impl TextOverlay {
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// 生成逗号连接的 drawtext 滤镜，没有叠加项时返回 None
+    pub fn filter_chain(&self) -> Option<String> {
+        if self.items.is_empty() {
+            return None;
+        }
+        let hostname = self.items.contains(&OverlayItem::Hostname).then(hostname);
+        let line_height = self.font_size + 2 * BOX_BORDER + 4;
+        let count = self.items.len() as u32;
+
+        let filters: Vec<String> = self
+            .items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let line = i as u32;
+                let y = if self.position.is_top() {
+                    format!("{}", MARGIN + BOX_BORDER + line * line_height)
+                } else {
+                    format!("h-th-{}", MARGIN + BOX_BORDER + (count - 1 - line) * line_height)
+                };
+                let text = match item {
+                    OverlayItem::Timestamp => "text='%{localtime\\:%Y-%m-%d %T}'".to_string(),
+                    OverlayItem::FrameCounter => "text='#%{n}'".to_string(),
+                    OverlayItem::Hostname => literal_text(hostname.as_deref().unwrap_or_default()),
+                    OverlayItem::Text(t) => literal_text(t),
+                };
+                format!("drawtext={}:{}:x={}:y={}", self.style(), text, self.position.x(), y)
+            })
+            .collect();
+        Some(filters.join(","))
+    }
+
+    /// 字体、字号、颜色与背景框参数
+    fn style(&self) -> String {
+        let mut style = Vec::new();
+        match self.font.as_deref() {
+            Some(font) if looks_like_path(font) => style.push(format!("fontfile={}", escape_filter_path(font))),
+            Some(font) => style.push(format!("font={}", literal_value(font))),
+            None if cfg!(windows) => style.push(format!("fontfile={}", escape_filter_path(WINDOWS_DEFAULT_FONT))),
+            None => {}
+        }
+        style.push(format!("fontsize={}", self.font_size));
+        style.push(format!("fontcolor={}", literal_value(&self.font_color)));
+        if let Some(color) = &self.box_color {
+            style.push(format!("box=1:boxcolor={}:boxborderw={}", literal_value(color), BOX_BORDER));
+        }
+        style.join(":")
+    }
+
+    /// `latency_probe` 为 true 时检查与延迟测量时间戳（左上角）的重叠
+    pub fn validate(&self, ffmpeg: &FfmpegCapabilities, latency_probe: bool, report: &mut ValidationReport) {
+        if self.items.is_empty() {
+            return;
+        }
+        if let Err(e) = ffmpeg.ensure_filter("drawtext") {
+            report.reject("overlay", "drawtext", e);
+        }
+        if !FONT_SIZE_RANGE.contains(&self.font_size) {
+            report
+                .error("overlay_size", self.font_size, "字号超出允许范围")
+                .suggest(format!("取 {} - {} 像素", FONT_SIZE_RANGE.start(), FONT_SIZE_RANGE.end()));
+        }
+        if let Some(font) = self.font.as_deref().filter(|f| looks_like_path(f)) {
+            if !Path::new(font).is_file() {
+                report
+                    .error("overlay_font", font, "字体文件不存在")
+                    .suggest("指定 .ttf/.otf/.ttc 文件路径，或直接使用字体名称（需要 fontconfig）");
+            } else if font.contains('\'') {
+                report
+                    .error("overlay_font", font, "路径中含有单引号，无法写入滤镜参数")
+                    .suggest("将字体移动到不含单引号的路径");
+            }
+        }
+        for item in &self.items {
+            if let OverlayItem::Text(t) = item {
+                if t.trim().is_empty() {
+                    report.error("overlay_text", t, "叠加文字为空");
+                }
+            }
+        }
+        if latency_probe && self.position == OverlayPosition::TopLeft {
+            report
+                .warning("overlay_position", self.position, "与延迟测量时间戳重叠")
+                .suggest("改用 --overlay-position top-right 等其他角落");
+        }
+    }
+}
+
+/// 按文件路径写法（含路径分隔符或字体扩展名）区分字体文件与字体名称
+fn looks_like_path(font: &str) -> bool {
+    let lower = font.to_ascii_lowercase();
+    font.contains(['/', '\\']) || [".ttf", ".otf", ".ttc"].iter().any(|ext| lower.ends_with(ext))
+}
+
+/// 原样显示的文字：关闭 `%{...}` 展开并转义
+fn literal_text(text: &str) -> String {
+    format!("expansion=none:text={}", literal_value(text))
+}
+
+/// 转义滤镜参数值：先转义选项层的 `\ ' :`，再转义滤镜图层的 `\ ' [ ] , ;`
+pub fn literal_value(value: &str) -> String {
+    let mut option = String::new();
+    for c in value.chars() {
+        if matches!(c, '\\' | '\'' | ':') {
+            option.push('\\');
+        }
+        option.push(c);
+    }
+    let mut graph = String::new();
+    for c in option.chars() {
+        if matches!(c, '\\' | '\'' | '[' | ']' | ',' | ';') {
+            graph.push('\\');
+        }
+        graph.push(c);
+    }
+    graph
+}
+
+/// 本机主机名：依次尝试环境变量、/proc 与 hostname 命令
+fn hostname() -> String {
+    let from_env = ["COMPUTERNAME", "HOSTNAME"].iter().find_map(|k| std::env::var(k).ok());
+    let from_proc = || std::fs::read_to_string("/proc/sys/kernel/hostname").ok();
+    let from_command = || {
+        std::process::Command::new("hostname")
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+    };
+    from_env
+        .or_else(from_proc)
+        .or_else(from_command)
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
@@ -0,0 +1,334 @@
+/// 画中画：将第二路视频（摄像头、文件或网络流）叠加到屏幕画面上
+///
+/// 第二路输入按墙钟时间打时间戳，与屏幕采集的时间基准一致，overlay 按时间戳对齐两路画面；
+/// 本地文件以原始速度循环读取，便于没有摄像头时测试。
+/// 画中画缩放并裁剪到固定尺寸，圆角与圆形通过 geq 生成透明度实现，边框为同形状的纯色底板。
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::RecorderError;
+use crate::ffmpeg_encoder::FfmpegCapabilities;
+use crate::filter_graph::{FilterGraph, Pad};
+use crate::overlay::{literal_value, OverlayPosition};
+use crate::validation::ValidationReport;
+
+/// 边框宽度上限（像素）
+const MAX_BORDER: u32 = 50;
+/// 画中画最小边长（像素）
+const MIN_SIZE: u32 = 16;
+/// 圆角半径占短边的比例
+const ROUNDED_RADIUS_DIVISOR: u32 = 8;
+
+/// 画中画形状
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PipShape {
+    #[default]
+    Rectangle,
+    Rounded,
+    /// 以短边为直径的圆形
+    Circle,
+}
+
+impl fmt::Display for PipShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PipShape::Rectangle => "rect",
+            PipShape::Rounded => "rounded",
+            PipShape::Circle => "circle",
+        })
+    }
+}
+
+impl FromStr for PipShape {
+    type Err = RecorderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "rect" | "rectangle" => Ok(PipShape::Rectangle),
+            "rounded" => Ok(PipShape::Rounded),
+            "circle" => Ok(PipShape::Circle),
+            _ => Err(RecorderError::InvalidOverlay(format!("未知的画中画形状 {}，可选 rect, rounded, circle", s))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PictureInPicture {
+    /// FFmpeg 输入：设备（如 /dev/video0）、文件路径或网络流地址
+    pub input: String,
+    /// 输入格式（如 v4l2、dshow），None 时由 FFmpeg 自动识别
+    pub format: Option<String>,
+    pub position: OverlayPosition,
+    /// 画中画尺寸（输出分辨率下的像素）
+    pub width: u32,
+    pub height: u32,
+    /// 到画面边缘的距离（像素）
+    pub margin: u32,
+    /// 边框宽度（像素），0 表示无边框
+    pub border: u32,
+    pub border_color: String,
+    pub shape: PipShape,
+}
+
+impl PictureInPicture {
+    /// 未指定输入格式且不是网络地址时视为本地文件
+    fn is_file(&self) -> bool {
+        self.format.is_none() && !self.input.contains("://")
+    }
+
+    /// 圆形取短边为直径
+    fn size(&self) -> (u32, u32) {
+        match self.shape {
+            PipShape::Circle => {
+                let side = self.width.min(self.height);
+                (side, side)
+            }
+            _ => (self.width, self.height),
+        }
+    }
+
+    fn corner_radius(&self) -> Option<u32> {
+        let (w, h) = self.size();
+        match self.shape {
+            PipShape::Rectangle => None,
+            PipShape::Rounded => Some((w.min(h) / ROUNDED_RADIUS_DIVISOR).max(1)),
+            PipShape::Circle => Some(w / 2),
+        }
+    }
+
+    fn required_filters(&self) -> Vec<&'static str> {
+        let mut filters = vec!["scale", "crop", "setsar", "overlay"];
+        if self.shape != PipShape::Rectangle {
+            filters.extend(["format", "geq"]);
+        }
+        if self.border > 0 {
+            filters.push("color");
+        }
+        filters
+    }
+
+    /// 输入参数，位于 `-i` 之前的选项一并给出
+    pub fn input_args(&self) -> Vec<String> {
+        let mut args = vec!["-thread_queue_size".to_string(), "512".to_string()];
+        if self.is_file() {
+            // 文件按原始速度循环读取，模拟实时画面
+            args.extend(["-re", "-stream_loop", "-1"].map(String::from));
+        }
+        args.extend(["-use_wallclock_as_timestamps", "1"].map(String::from));
+        if let Some(format) = &self.format {
+            args.extend(["-f".to_string(), format.clone()]);
+        }
+        args.extend(["-i".to_string(), self.input.clone()]);
+        args
+    }
+
+    /// 将第 `input` 路输入叠加到 `main` 上，返回合成后的端口
+    pub fn compose(&self, graph: &mut FilterGraph, main: &Pad, input: usize, fps: u32) -> Pad {
+        let (w, h) = self.size();
+        let radius = self.corner_radius();
+
+        let mut filters = vec![
+            format!("scale={}:{}:force_original_aspect_ratio=increase", w, h),
+            format!("crop={}:{}", w, h),
+            "setsar=1".to_string(),
+        ];
+        if let Some(r) = radius {
+            filters.push("format=yuva420p".to_string());
+            filters.push(alpha_mask(r));
+        }
+        let mut pip = graph.link(&[&Pad::video_input(input)], &filters, "pip");
+
+        // 边框：画中画居中叠加到比它大一圈的同形状纯色底板上
+        if self.border > 0 {
+            let b = self.border;
+            let mut frame = vec![format!(
+                "color=c={}:s={}x{}:r={}",
+                literal_value(&self.border_color),
+                w + 2 * b,
+                h + 2 * b,
+                fps
+            )];
+            if let Some(r) = radius {
+                frame.push("format=yuva420p".to_string());
+                frame.push(alpha_mask(r + b));
+            }
+            let frame = graph.source(&frame, "pipframe");
+            pip = graph.link(&[&frame, &pip], &[format!("overlay=x={}:y={}:shortest=1", b, b)], "pip");
+        }
+
+        let x = if self.position.is_left() {
+            self.margin.to_string()
+        } else {
+            format!("W-w-{}", self.margin)
+        };
+        let y = if self.position.is_top() {
+            self.margin.to_string()
+        } else {
+            format!("H-h-{}", self.margin)
+        };
+        graph.link(&[main, &pip], &[format!("overlay=x={}:y={}", x, y)], "v")
+    }
+
+    /// `output_size` 为输出分辨率，用于检查画中画能否放下
+    pub fn validate(&self, ffmpeg: &FfmpegCapabilities, output_size: Option<(u32, u32)>, report: &mut ValidationReport) {
+        if self.input.trim().is_empty() {
+            report.error("pip", "", "画中画输入为空");
+        } else if self.is_file() && !Path::new(&self.input).exists() {
+            report
+                .error("pip", &self.input, "画中画输入文件不存在")
+                .suggest("摄像头等设备请同时指定 --pip-format（如 v4l2、dshow）");
+        }
+        if let Some(format) = &self.format {
+            if !ffmpeg.has_input_device(format) && !ffmpeg.has_muxer(format) {
+                report.warning("pip_format", format, "当前 FFmpeg 可能不支持该输入格式");
+            }
+        }
+        for filter in self.required_filters() {
+            if let Err(e) = ffmpeg.ensure_filter(filter) {
+                report.reject("pip", filter, e);
+            }
+        }
+
+        let (w, h) = self.size();
+        if w < MIN_SIZE || h < MIN_SIZE {
+            report
+                .error("pip_size", format!("{}x{}", self.width, self.height), "画中画尺寸过小")
+                .suggest(format!("宽高至少 {} 像素", MIN_SIZE));
+        }
+        if self.border > MAX_BORDER {
+            report
+                .error("pip_border", self.border, "边框过宽")
+                .suggest(format!("取 0 - {} 像素", MAX_BORDER));
+        }
+        if let Some((out_w, out_h)) = output_size {
+            let need_w = w + 2 * self.border + self.margin;
+            let need_h = h + 2 * self.border + self.margin;
+            if need_w > out_w || need_h > out_h {
+                report
+                    .error(
+                        "pip_size",
+                        format!("{}x{}", self.width, self.height),
+                        format!("加上边框与边距后超出 {}x{} 的输出画面", out_w, out_h),
+                    )
+                    .suggest("减小 --pip-size、--pip-border 或 --pip-margin");
+            }
+        }
+    }
+}
+
+/// 解析 `WIDTHxHEIGHT` 写法的尺寸
+pub fn parse_size(s: &str) -> Option<(u32, u32)> {
+    let (w, h) = s.split_once(['x', 'X'])?;
+    Some((w.trim().parse().ok()?, h.trim().parse().ok()?))
+}
+
+/// 按圆角矩形生成透明度（半径为短边一半时即为圆形），`r` 为圆角半径
+///
+/// 表达式整体加引号，逗号不会被滤镜图当作分隔符。
+fn alpha_mask(r: u32) -> String {
+    format!(
+        "geq=lum='lum(X,Y)':cb='cb(X,Y)':cr='cr(X,Y)':a='if(lte(hypot(max(0,abs(X-W/2)-(W/2-{0})),max(0,abs(Y-H/2)-(H/2-{0}))),{0}),255,0)'",
+        r
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pip(input: &str, shape: PipShape, border: u32) -> PictureInPicture {
+        PictureInPicture {
+            input: input.to_string(),
+            format: None,
+            position: OverlayPosition::BottomRight,
+            width: 320,
+            height: 180,
+            margin: 16,
+            border,
+            border_color: "white".to_string(),
+            shape,
+        }
+    }
+
+    fn compose(pip: &PictureInPicture) -> String {
+        let mut graph = FilterGraph::default();
+        pip.compose(&mut graph, &Pad::video_input(0), 2, 30);
+        graph.to_string()
+    }
+
+    #[test]
+    fn file_input_loops_at_native_speed() {
+        let args = pip("cam.mp4", PipShape::Rectangle, 0).input_args();
+        assert_eq!(
+            args,
+            ["-thread_queue_size", "512", "-re", "-stream_loop", "-1", "-use_wallclock_as_timestamps", "1", "-i", "cam.mp4"]
+        );
+    }
+
+    #[test]
+    fn device_and_network_inputs_are_read_live() {
+        let mut camera = pip("/dev/video0", PipShape::Rectangle, 0);
+        camera.format = Some("v4l2".to_string());
+        assert_eq!(
+            camera.input_args(),
+            ["-thread_queue_size", "512", "-use_wallclock_as_timestamps", "1", "-f", "v4l2", "-i", "/dev/video0"]
+        );
+
+        let stream = pip("rtsp://cam.local/live", PipShape::Rectangle, 0).input_args();
+        assert!(!stream.contains(&"-re".to_string()));
+        assert!(!stream.contains(&"-stream_loop".to_string()));
+    }
+
+    #[test]
+    fn rectangle_overlays_scaled_input() {
+        assert_eq!(
+            compose(&pip("cam.mp4", PipShape::Rectangle, 0)),
+            "[2:v]scale=320:180:force_original_aspect_ratio=increase,crop=320:180,setsar=1[pip1];\
+             [0:v][pip1]overlay=x=W-w-16:y=H-h-16[v2]"
+        );
+    }
+
+    #[test]
+    fn border_sits_on_larger_backdrop() {
+        assert_eq!(
+            compose(&pip("cam.mp4", PipShape::Rectangle, 4)),
+            "[2:v]scale=320:180:force_original_aspect_ratio=increase,crop=320:180,setsar=1[pip1];\
+             color=c=white:s=328x188:r=30[pipframe2];\
+             [pipframe2][pip1]overlay=x=4:y=4:shortest=1[pip3];\
+             [0:v][pip3]overlay=x=W-w-16:y=H-h-16[v4]"
+        );
+    }
+
+    #[test]
+    fn rounded_corners_use_alpha_mask() {
+        let graph = compose(&pip("cam.mp4", PipShape::Rounded, 4));
+        // 圆角半径为短边的 1/8，底板半径再加上边框宽度
+        assert!(graph.contains(&format!("setsar=1,format=yuva420p,{}[pip1]", alpha_mask(22))));
+        assert!(graph.contains(&format!("color=c=white:s=328x188:r=30,format=yuva420p,{}[pipframe2]", alpha_mask(26))));
+    }
+
+    #[test]
+    fn circle_uses_short_side_as_diameter() {
+        let graph = compose(&pip("cam.mp4", PipShape::Circle, 0));
+        assert_eq!(
+            graph,
+            format!(
+                "[2:v]scale=180:180:force_original_aspect_ratio=increase,crop=180:180,setsar=1,format=yuva420p,{}[pip1];\
+                 [0:v][pip1]overlay=x=W-w-16:y=H-h-16[v2]",
+                alpha_mask(90)
+            )
+        );
+    }
+
+    #[test]
+    fn parse_size_accepts_width_by_height() {
+        assert_eq!(parse_size("320x180"), Some((320, 180)));
+        assert_eq!(parse_size("640X360"), Some((640, 360)));
+        assert_eq!(parse_size(" 480 x 270 "), Some((480, 270)));
+        assert_eq!(parse_size("320"), None);
+        assert_eq!(parse_size("320x"), None);
+        assert_eq!(parse_size("axb"), None);
+        assert_eq!(parse_size("-320x180"), None);
+    }
+}
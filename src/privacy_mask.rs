@@ -0,0 +1,306 @@
+/// 隐私遮挡：对画面中固定区域做模糊、马赛克或纯色填充
+///
+/// 坐标相对于采集区域（缩放之前），遮挡在所有叠加之前完成，文字与水印不会被遮住。
+/// FFmpeg 采集时每个区域用 split/crop/overlay 处理后贴回原位，区域必须完整落在采集区域内；
+/// 原生捕获直接修改 BGRA 帧，超出显示器的部分被裁掉。
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::RecorderError;
+use crate::ffmpeg_encoder::FfmpegCapabilities;
+use crate::filter_graph::{FilterGraph, Pad};
+use crate::validation::ValidationReport;
+
+/// 模糊强度（FFmpeg gblur 的 sigma）
+const BLUR_SIGMA: u32 = 20;
+/// 原生捕获的盒式模糊半径，三次迭代近似高斯模糊
+const BLUR_RADIUS: usize = 12;
+const BLUR_PASSES: usize = 3;
+/// 马赛克色块边长（像素）
+const PIXEL_BLOCK: u32 = 16;
+
+/// 遮挡方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaskStyle {
+    Blur,
+    Pixelate,
+    /// 纯色填充（RGB）
+    Fill([u8; 3]),
+}
+
+impl MaskStyle {
+    fn required_filters(&self) -> &'static [&'static str] {
+        match self {
+            MaskStyle::Blur => &["split", "crop", "gblur", "overlay"],
+            MaskStyle::Pixelate => &["split", "crop", "scale", "overlay"],
+            MaskStyle::Fill(_) => &["drawbox"],
+        }
+    }
+}
+
+impl fmt::Display for MaskStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaskStyle::Blur => f.write_str("blur"),
+            MaskStyle::Pixelate => f.write_str("pixelate"),
+            MaskStyle::Fill([r, g, b]) => write!(f, "fill=#{:02X}{:02X}{:02X}", r, g, b),
+        }
+    }
+}
+
+impl FromStr for MaskStyle {
+    type Err = RecorderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (style, color) = s.split_once('=').unwrap_or((s, "black"));
+        match style.to_ascii_lowercase().as_str() {
+            "blur" => Ok(MaskStyle::Blur),
+            "pixelate" | "mosaic" => Ok(MaskStyle::Pixelate),
+            "fill" | "solid" => parse_color(color).map(MaskStyle::Fill),
+            _ => Err(RecorderError::InvalidMask(s.to_string())),
+        }
+    }
+}
+
+/// 颜色：black / white / gray 或 #RRGGBB
+fn parse_color(s: &str) -> Result<[u8; 3], RecorderError> {
+    match s.to_ascii_lowercase().as_str() {
+        "black" => return Ok([0, 0, 0]),
+        "white" => return Ok([255, 255, 255]),
+        "gray" | "grey" => return Ok([128, 128, 128]),
+        _ => {}
+    }
+    let hex = s.trim_start_matches('#');
+    let channel = |i: usize| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+    match (hex.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) => Ok([r, g, b]),
+        _ => Err(RecorderError::InvalidMask(format!("无法识别的颜色 {}", s))),
+    }
+}
+
+/// 遮挡区域（像素，相对采集区域左上角）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrivacyMask {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub style: MaskStyle,
+}
+
+impl fmt::Display for PrivacyMask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{},{}:{}", self.x, self.y, self.width, self.height, self.style)
+    }
+}
+
+/// 命令行写法 `X,Y,W,H[:blur|pixelate|fill[=颜色]]`，省略方式时为模糊
+impl FromStr for PrivacyMask {
+    type Err = RecorderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rect, style) = s.split_once(':').unwrap_or((s, "blur"));
+        let parts: Vec<u32> = rect
+            .split(',')
+            .map(|v| v.trim().parse::<u32>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| RecorderError::InvalidMask(s.to_string()))?;
+        let [x, y, width, height] = parts[..] else {
+            return Err(RecorderError::InvalidMask(s.to_string()));
+        };
+        if width == 0 || height == 0 {
+            return Err(RecorderError::InvalidMask(s.to_string()));
+        }
+        Ok(PrivacyMask { x, y, width, height, style: style.parse()? })
+    }
+}
+
+impl PrivacyMask {
+    /// 将遮挡加入滤镜图：纯色直接 drawbox，模糊与马赛克复制一路画面裁出区域处理后贴回原位
+    fn compose(&self, graph: &mut FilterGraph, input: Pad) -> Pad {
+        let effect = match self.style {
+            MaskStyle::Fill([r, g, b]) => {
+                let drawbox = format!(
+                    "drawbox=x={}:y={}:w={}:h={}:color=0x{:02X}{:02X}{:02X}:t=fill",
+                    self.x, self.y, self.width, self.height, r, g, b
+                );
+                return graph.link(&[&input], &[drawbox], "masked");
+            }
+            MaskStyle::Blur => format!("gblur=sigma={}", BLUR_SIGMA),
+            // 先缩小再用最近邻放大回原尺寸，得到方块马赛克
+            MaskStyle::Pixelate => format!(
+                "scale=w=max(1\\,iw/{0}):h=max(1\\,ih/{0}):flags=area,scale=w={1}:h={2}:flags=neighbor",
+                PIXEL_BLOCK, self.width, self.height
+            ),
+        };
+        let (base, region) = graph.split(&input, "maskbase");
+        let crop = format!("crop=w={}:h={}:x={}:y={}", self.width, self.height, self.x, self.y);
+        let region = graph.link(&[&region], &[crop, effect], "mask");
+        let overlay = format!("overlay=x={}:y={}", self.x, self.y);
+        graph.link(&[&base, &region], &[overlay], "masked")
+    }
+
+    /// 直接修改 BGRA 帧，`stride` 为每行字节数
+    fn apply(&self, frame: &mut [u8], frame_width: usize, frame_height: usize, stride: usize) {
+        let x0 = self.x as usize;
+        let y0 = self.y as usize;
+        let w = (self.width as usize).min(frame_width.saturating_sub(x0));
+        let h = (self.height as usize).min(frame_height.saturating_sub(y0));
+        if w == 0 || h == 0 || frame.len() < (y0 + h - 1) * stride + (x0 + w) * 4 {
+            return;
+        }
+        let region = Region { x0, y0, w, h, stride };
+        match self.style {
+            MaskStyle::Fill([r, g, b]) => region.fill(frame, [b, g, r]),
+            MaskStyle::Pixelate => region.pixelate(frame, PIXEL_BLOCK as usize),
+            MaskStyle::Blur => {
+                for _ in 0..BLUR_PASSES {
+                    region.box_blur(frame, BLUR_RADIUS);
+                }
+            }
+        }
+    }
+}
+
+/// 依次加入全部遮挡区域，返回处理后的端口
+pub fn apply_graph(masks: &[PrivacyMask], graph: &mut FilterGraph, input: Pad) -> Pad {
+    masks.iter().fold(input, |pad, mask| mask.compose(graph, pad))
+}
+
+/// 对 BGRA 帧应用全部遮挡（原生捕获）
+pub fn apply(masks: &[PrivacyMask], frame: &mut [u8], width: usize, height: usize, stride: usize) {
+    for mask in masks {
+        mask.apply(frame, width, height, stride);
+    }
+}
+
+/// `capture_size` 为实际采集的画面尺寸，用于检查区域是否落在画面内；未知时跳过，由原生捕获在运行时裁剪
+pub fn validate(
+    masks: &[PrivacyMask],
+    capture_size: Option<(u32, u32)>,
+    ffmpeg: &FfmpegCapabilities,
+    report: &mut ValidationReport,
+) {
+    for mask in masks {
+        for filter in mask.style.required_filters() {
+            if let Err(e) = ffmpeg.ensure_filter(filter) {
+                report.reject("mask", mask, e);
+            }
+        }
+        let Some((width, height)) = capture_size else {
+            continue;
+        };
+        if mask.x >= width || mask.y >= height {
+            report
+                .error("mask", mask, format!("区域起点在 {}x{} 的采集区域之外", width, height))
+                .suggest("坐标相对采集区域左上角，原生捕获时以整个显示器为准");
+        } else if mask.x + mask.width > width || mask.y + mask.height > height {
+            report
+                .error("mask", mask, format!("区域超出 {}x{} 的采集区域", width, height))
+                .suggest(format!("宽度不超过 {}，高度不超过 {}", width - mask.x, height - mask.y));
+        }
+    }
+}
+
+/// 帧中的矩形区域
+struct Region {
+    x0: usize,
+    y0: usize,
+    w: usize,
+    h: usize,
+    stride: usize,
+}
+
+impl Region {
+    fn offset(&self, x: usize, y: usize) -> usize {
+        (self.y0 + y) * self.stride + (self.x0 + x) * 4
+    }
+
+    fn fill(&self, frame: &mut [u8], bgr: [u8; 3]) {
+        for y in 0..self.h {
+            let start = self.offset(0, y);
+            for px in frame[start..start + self.w * 4].chunks_exact_mut(4) {
+                px[..3].copy_from_slice(&bgr);
+            }
+        }
+    }
+
+    /// 每个色块取平均颜色
+    fn pixelate(&self, frame: &mut [u8], block: usize) {
+        for by in (0..self.h).step_by(block) {
+            for bx in (0..self.w).step_by(block) {
+                let bw = block.min(self.w - bx);
+                let bh = block.min(self.h - by);
+                let mut sum = [0u32; 3];
+                for y in by..by + bh {
+                    let start = self.offset(bx, y);
+                    for px in frame[start..start + bw * 4].chunks_exact(4) {
+                        for c in 0..3 {
+                            sum[c] += px[c] as u32;
+                        }
+                    }
+                }
+                let count = (bw * bh) as u32;
+                let average = sum.map(|s| (s / count) as u8);
+                for y in by..by + bh {
+                    let start = self.offset(bx, y);
+                    for px in frame[start..start + bw * 4].chunks_exact_mut(4) {
+                        px[..3].copy_from_slice(&average);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 水平、垂直各做一次滑动窗口平均，边缘按区域内的像素计算
+    fn box_blur(&self, frame: &mut [u8], radius: usize) {
+        let mut line = Vec::new();
+        for y in 0..self.h {
+            line.clear();
+            line.extend((0..self.w).map(|x| pixel(frame, self.offset(x, y))));
+            for (x, value) in blur_line(&line, radius).into_iter().enumerate() {
+                let i = self.offset(x, y);
+                frame[i..i + 3].copy_from_slice(&value);
+            }
+        }
+        for x in 0..self.w {
+            line.clear();
+            line.extend((0..self.h).map(|y| pixel(frame, self.offset(x, y))));
+            for (y, value) in blur_line(&line, radius).into_iter().enumerate() {
+                let i = self.offset(x, y);
+                frame[i..i + 3].copy_from_slice(&value);
+            }
+        }
+    }
+}
+
+fn pixel(frame: &[u8], i: usize) -> [u8; 3] {
+    [frame[i], frame[i + 1], frame[i + 2]]
+}
+
+fn blur_line(line: &[[u8; 3]], radius: usize) -> Vec<[u8; 3]> {
+    let len = line.len();
+    let mut sum = [0u32; 3];
+    let mut lo = 0;
+    let mut hi = 0;
+    let mut out = Vec::with_capacity(len);
+    for i in 0..len {
+        // 窗口为 [i - radius, i + radius]，与区域边界取交集
+        let (start, end) = (i.saturating_sub(radius), (i + radius + 1).min(len));
+        while hi < end {
+            for c in 0..3 {
+                sum[c] += line[hi][c] as u32;
+            }
+            hi += 1;
+        }
+        while lo < start {
+            for c in 0..3 {
+                sum[c] -= line[lo][c] as u32;
+            }
+            lo += 1;
+        }
+        let count = (hi - lo) as u32;
+        out.push(sum.map(|s| (s / count) as u8));
+    }
+    out
+}
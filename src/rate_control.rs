@@ -0,0 +1,200 @@
+/// 码率控制模式：默认本地录制使用 VBR、推流使用 CBR，可选恒定质量（CRF/CQP），
+/// 并按编码器映射为各自的 FFmpeg 参数
+use std::fmt;
+
+use crate::bitrate::Bitrate;
+use crate::codec::VideoCodec;
+use crate::error::RecorderError;
+use crate::validation::ValidationReport;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RateControl {
+    /// 恒定质量（x264/x265 的 CRF，VP9/AV1 的 constant quality），数值越小画质越高
+    Crf(u32),
+    /// 恒定量化参数，码率随画面复杂度大幅波动，适合作为中间素材
+    Cqp(u32),
+    /// 平均码率，可选峰值码率与缓冲区
+    Vbr {
+        maxrate: Option<Bitrate>,
+        bufsize: Option<Bitrate>,
+    },
+    /// 恒定码率，推流平台通常要求；未指定缓冲区时为 2 倍码率
+    Cbr { bufsize: Option<Bitrate> },
+}
+
+impl RateControl {
+    /// 根据命令行参数构建码率控制模式
+    ///
+    /// 未指定模式时保持原有行为：推流使用 CBR，录制使用 VBR；未知模式记入报告并回退到默认模式。
+    pub fn from_args(
+        mode: Option<&str>,
+        quality: Option<u32>,
+        maxrate: Option<Bitrate>,
+        bufsize: Option<Bitrate>,
+        codec: VideoCodec,
+        streaming: bool,
+        report: &mut ValidationReport,
+    ) -> RateControl {
+        let default_mode = if streaming { "cbr" } else { "vbr" };
+        let lower = mode.map(str::to_ascii_lowercase);
+
+        let rc = match lower.as_deref().unwrap_or(default_mode) {
+            "crf" => RateControl::Crf(quality.unwrap_or_else(|| codec.default_quality())),
+            "cqp" | "qp" => RateControl::Cqp(quality.unwrap_or_else(|| codec.default_quality())),
+            "vbr" | "abr" => RateControl::Vbr { maxrate, bufsize },
+            "cbr" => RateControl::Cbr { bufsize },
+            other => {
+                report
+                    .reject(
+                        "rate_control",
+                        other,
+                        RecorderError::InvalidRateControl(format!("未知模式 {}", other)),
+                    )
+                    .suggest("可选 crf, cqp, vbr, cbr");
+                return RateControl::from_args(None, quality, maxrate, bufsize, codec, streaming, report);
+            }
+        };
+
+        if let Some(q) = quality {
+            if !rc.is_constant_quality() {
+                report
+                    .warning("quality", q, format!("仅在 crf/cqp 模式下生效，当前为 {}", rc))
+                    .suggest("配合 --rate-control crf 使用");
+            }
+        }
+        rc
+    }
+
+    /// 检查该模式与编码器的组合、质量参数范围，以及 VBR 峰值码率不低于目标码率
+    pub fn validate(&self, codec: VideoCodec, bitrate: Bitrate, report: &mut ValidationReport) {
+        if let RateControl::Vbr { maxrate: Some(maxrate), .. } = self {
+            if *maxrate < bitrate {
+                report
+                    .error("maxrate", maxrate, format!("低于目标码率 {}", bitrate))
+                    .suggest(format!("取不低于 {} 的值，或去掉 --maxrate", bitrate));
+            }
+        }
+        if let RateControl::Crf(q) | RateControl::Cqp(q) = self {
+            let max = codec.max_quality();
+            if *q > max {
+                report
+                    .error("quality", q, format!("{} 的质量参数超出范围", codec))
+                    .suggest(format!("取 0-{} 之间的值，默认 {}", max, codec.default_quality()));
+            }
+        }
+        if matches!(self, RateControl::Cqp(_)) && matches!(codec, VideoCodec::Vp9 | VideoCodec::Av1Aom) {
+            report
+                .error("rate_control", "cqp", format!("{} 不支持 cqp", codec))
+                .suggest("改用 crf");
+        }
+    }
+
+    /// 未显式设置缓冲区时使用给定值（低延迟模式缩小 VBV 缓冲）
+    pub fn set_default_bufsize(&mut self, value: Bitrate) {
+        match self {
+            RateControl::Vbr { bufsize, .. } | RateControl::Cbr { bufsize } => {
+                bufsize.get_or_insert(value);
+            }
+            RateControl::Crf(_) | RateControl::Cqp(_) => {}
+        }
+    }
+
+    /// 是否为恒定质量类模式（不以码率为目标）
+    pub fn is_constant_quality(&self) -> bool {
+        matches!(self, RateControl::Crf(_) | RateControl::Cqp(_))
+    }
+
+    /// 生成编码器对应的码率控制参数
+    pub fn encoder_args(&self, codec: VideoCodec, bitrate: Bitrate) -> Vec<String> {
+        let mut args: Vec<String> = Vec::new();
+        let mut push = |k: &str, v: &str| {
+            args.push(k.to_string());
+            args.push(v.to_string());
+        };
+
+        match (self, codec) {
+            // x264 / x265
+            (RateControl::Crf(q), VideoCodec::H264 | VideoCodec::H265) => push("-crf", &q.to_string()),
+            (RateControl::Cqp(q), VideoCodec::H264 | VideoCodec::H265) => push("-qp", &q.to_string()),
+
+            // VP9 / libaom：恒定质量需要将 -b:v 置 0
+            (RateControl::Crf(q), VideoCodec::Vp9 | VideoCodec::Av1Aom) => {
+                push("-crf", &q.to_string());
+                push("-b:v", "0");
+            }
+
+            // SVT-AV1
+            (RateControl::Crf(q), VideoCodec::Av1Svt) => push("-crf", &q.to_string()),
+            (RateControl::Cqp(q), VideoCodec::Av1Svt) => push("-qp", &q.to_string()),
+
+            // NVENC
+            (RateControl::Crf(q), VideoCodec::H264Nvenc | VideoCodec::HevcNvenc) => {
+                push("-rc", "vbr");
+                push("-cq", &q.to_string());
+                push("-b:v", "0");
+            }
+            (RateControl::Cqp(q), VideoCodec::H264Nvenc | VideoCodec::HevcNvenc) => {
+                push("-rc", "constqp");
+                push("-qp", &q.to_string());
+            }
+
+            // Quick Sync：ICQ 与 CQP
+            (RateControl::Crf(q), VideoCodec::H264Qsv) => push("-global_quality", &q.to_string()),
+            (RateControl::Cqp(q), VideoCodec::H264Qsv) => push("-q:v", &q.to_string()),
+
+            // validate 已拒绝的组合，退化为 CRF
+            (RateControl::Cqp(q), VideoCodec::Vp9 | VideoCodec::Av1Aom) => {
+                push("-crf", &q.to_string());
+                push("-b:v", "0");
+            }
+
+            (RateControl::Vbr { maxrate, bufsize }, _) => {
+                if matches!(codec, VideoCodec::H264Nvenc | VideoCodec::HevcNvenc) {
+                    push("-rc", "vbr");
+                }
+                push("-b:v", &bitrate.to_string());
+                if let Some(m) = maxrate {
+                    push("-maxrate", &m.to_string());
+                    push("-bufsize", &bufsize.unwrap_or(*m * 2).to_string());
+                } else if let Some(b) = bufsize {
+                    push("-bufsize", &b.to_string());
+                }
+            }
+
+            (RateControl::Cbr { bufsize }, _) => {
+                // 默认缓冲区为 2 倍码率，允许短时码率波动
+                let bufsize = bufsize.unwrap_or(bitrate * 2);
+                let bitrate = bitrate.to_string();
+                if matches!(codec, VideoCodec::H264Nvenc | VideoCodec::HevcNvenc) {
+                    push("-rc", "cbr");
+                }
+                push("-b:v", &bitrate);
+                // libvpx/libaom/x264 在 minrate = maxrate = 目标码率时进入 CBR，
+                // SVT-AV1 只需 maxrate = 目标码率
+                if matches!(codec, VideoCodec::Vp9 | VideoCodec::Av1Aom | VideoCodec::H264) {
+                    push("-minrate", &bitrate);
+                }
+                push("-maxrate", &bitrate);
+                push("-bufsize", &bufsize.to_string());
+                if codec == VideoCodec::H264 {
+                    // 写入 HRD 信息，便于推流服务器识别为 CBR
+                    push("-x264-params", "nal-hrd=cbr");
+                }
+            }
+        }
+
+        args
+    }
+}
+
+impl fmt::Display for RateControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateControl::Crf(q) => write!(f, "crf {}", q),
+            RateControl::Cqp(q) => write!(f, "cqp {}", q),
+            RateControl::Vbr { .. } => f.write_str("vbr"),
+            RateControl::Cbr { .. } => f.write_str("cbr"),
+        }
+    }
+}
+
@@ -0,0 +1,125 @@
+/// 崩溃安全录制与中断文件恢复
+///
+/// 普通 MP4 的 moov 索引在录制结束时才写入，FFmpeg 被杀或机器断电后文件无法播放。
+/// 崩溃安全模式下先写入分片 MP4（moov 前置，每个关键帧一个分片），任何时刻中断
+/// 都只会丢失最后一个分片；正常结束后再无损重封装为带 faststart 的普通 MP4。
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use crate::config::{RecorderConfig, StreamProtocol};
+use crate::container::Container;
+use crate::ffmpeg_encoder::FfmpegCapabilities;
+
+/// 分片 MP4 的 movflags：空 moov 前置 + 按关键帧分片
+pub const FRAGMENTED_MOVFLAGS: &str = "frag_keyframe+empty_moov+default_base_moof";
+
+/// 录制中间文件的后缀
+const PARTIAL_SUFFIX: &str = ".part";
+
+/// 若当前配置启用了崩溃安全录制，返回录制过程中实际写入的中间文件路径
+pub fn partial_output(config: &RecorderConfig) -> Option<PathBuf> {
+    if !config.crash_safe || config.protocol != StreamProtocol::File {
+        return None;
+    }
+    // 崩溃安全模式仅对 MP4 系列容器有意义（Matroska/TS 等本身即可中断播放）
+    if !config.container().is_some_and(|c| c.is_isobmff()) {
+        return None;
+    }
+    Some(PathBuf::from(format!("{}{}", config.output, PARTIAL_SUFFIX)))
+}
+
+/// 启用崩溃安全但输出容器不适用时给出提示
+pub fn warn_if_unsupported(config: &RecorderConfig) {
+    if config.crash_safe
+        && config.protocol == StreamProtocol::File
+        && partial_output(config).is_none()
+    {
+        warn!("崩溃安全模式仅适用于 MP4/MOV 输出，{} 将按普通方式写入", config.output);
+    }
+}
+
+/// 正常结束后将分片文件重封装为最终输出，成功后删除中间文件
+pub fn finalize(
+    ffmpeg: &FfmpegCapabilities,
+    partial: &Path,
+    output: &str,
+    container: Container,
+) -> Result<()> {
+    if !partial.exists() {
+        bail!("未找到录制中间文件: {}", partial.display());
+    }
+
+    info!("📦 正在重封装为最终 {}: {}", container.name().to_uppercase(), output);
+    if let Err(e) = remux(ffmpeg, partial, Path::new(output), container) {
+        warn!(
+            "重封装失败，已保留中间文件 {}，可稍后使用 `recover` 子命令恢复",
+            partial.display()
+        );
+        return Err(e);
+    }
+
+    std::fs::remove_file(partial)
+        .with_context(|| format!("无法删除中间文件: {}", partial.display()))?;
+    Ok(())
+}
+
+/// 将中断的分片录制文件恢复为可播放的 MP4
+///
+/// 未指定输出时去掉 `.part` 后缀；输入不带该后缀时输出为 `<名称>.recovered.mp4`。
+pub fn recover(ffmpeg: &FfmpegCapabilities, input: &str, output: Option<&str>) -> Result<PathBuf> {
+    let input = Path::new(input);
+    if !input.exists() {
+        bail!("待恢复的文件不存在: {}", input.display());
+    }
+
+    let output = match output {
+        Some(o) => PathBuf::from(o),
+        None => default_recovered_path(input),
+    };
+    if output == input {
+        bail!("恢复输出不能与输入相同: {}", input.display());
+    }
+
+    info!("🩹 恢复中断的录制: {} -> {}", input.display(), output.display());
+    remux(ffmpeg, input, &output, Container::Mp4)?;
+    info!("✅ 恢复完成: {}", output.display());
+    Ok(output)
+}
+
+fn default_recovered_path(input: &Path) -> PathBuf {
+    let name = input.to_string_lossy();
+    match name.strip_suffix(PARTIAL_SUFFIX) {
+        Some(stripped) if !stripped.is_empty() => PathBuf::from(stripped),
+        _ => input.with_extension("recovered.mp4"),
+    }
+}
+
+/// 无损重封装（-c copy）为带 faststart 的 MP4/MOV，忽略截断的最后一个分片
+fn remux(ffmpeg: &FfmpegCapabilities, input: &Path, output: &Path, container: Container) -> Result<()> {
+    let out = ffmpeg
+        .command()
+        .arg("-hide_banner")
+        .arg("-err_detect").arg("ignore_err")
+        .arg("-i").arg(input)
+        .arg("-map").arg("0")
+        .arg("-c").arg("copy")
+        .args(container.output_args())
+        .arg("-y")
+        .arg(output)
+        .stdin(Stdio::null())
+        .output()
+        .context("无法启动 FFmpeg 进行重封装")?;
+
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        let tail: Vec<&str> = stderr.lines().rev().take(5).collect();
+        bail!(
+            "FFmpeg 重封装失败 ({}): {}",
+            out.status,
+            tail.into_iter().rev().collect::<Vec<_>>().join(" | ")
+        );
+    }
+    Ok(())
+}
@@ -1,52 +1,110 @@
 use crate::config::RecorderConfig;
+use crate::audio_control::AudioControl;
+use crate::container::Container;
+use crate::level_meter::LevelMeter;
+use crate::filter_graph::{self, FilterGraph, VideoSource};
+use crate::{ffmpeg_encoder, loudness, recovery};
 use anyhow::Result;
-use log::info;
+use log::{error, info, warn};
 use std::process::{Command, Stdio};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
 
 pub async fn start_recording(config: RecorderConfig) -> Result<()> {
-    info!("🎥 开始屏幕录制...");
+    recovery::warn_if_unsupported(&config);
 
-    let (_width, _height) = config.get_resolution_parts()?;
-    let input_format = config.get_input_format();
-    let device = config.get_capture_device();
-
-    // 构建 FFmpeg 命令
-    let mut ffmpeg_cmd = build_ffmpeg_command(&config, input_format, &device)?;
+    // 构建 FFmpeg 命令（纯音频模式不采集屏幕）
+    let mut ffmpeg_cmd = if config.audio_only {
+        info!("🎙️ 开始录音...");
+        ffmpeg_encoder::build_audio_only_command(&config)
+    } else {
+        info!("🎥 开始屏幕录制...");
+        let (_width, _height) = config.get_resolution_parts()?;
+        let input_format = config.get_input_format();
+        let device = config.get_capture_device();
+        build_ffmpeg_command(&config, input_format, &device)?
+    };
 
     info!("📝 FFmpeg 命令: {:?}", ffmpeg_cmd);
 
-    // 执行 FFmpeg
+    // 执行 FFmpeg（stdin 用于发送 `q` 以优雅结束，保证文件尾部完整写入）
     let mut child = ffmpeg_cmd
+        .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()?;
 
+    let meter = LevelMeter::new(&config);
+    ffmpeg_encoder::log_stderr(&mut child, meter.clone());
+    let control = AudioControl::start(&config, &mut child, meter.clone());
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
     // 如果设置了时间限制，等待指定时间后终止
     if config.duration > 0 {
         let duration = Duration::from_secs(config.duration as u64);
         info!("⏱️  录制时间限制: {:?}", duration);
-
-        sleep(duration).await;
-        
-        info!("⏸️  停止录制...");
-        child.kill()?;
     } else {
         info!("⏳ 无时间限制，按 Ctrl+C 停止录制");
-        let _ = child.wait()?;
     }
+    let deadline = (config.duration > 0)
+        .then(|| Instant::now() + Duration::from_secs(config.duration as u64));
+
+    // 静音超时终止时先正常结束录制，保留已录制的内容后再返回错误
+    let mut aborted = None;
+    let stop = loop {
+        tokio::select! {
+            _ = &mut ctrl_c => {
+                info!("⏸️  收到 Ctrl+C，停止录制...");
+                break true;
+            }
+            _ = sleep(Duration::from_millis(200)) => {
+                if child.try_wait()?.is_some() {
+                    break false;
+                }
+                if let Some(Err(e)) = meter.as_ref().map(|m| m.poll()) {
+                    error!("🔇 {}", e);
+                    aborted = Some(e);
+                    break true;
+                }
+                if deadline.is_some_and(|d| Instant::now() >= d) {
+                    info!("⏸️  停止录制...");
+                    break true;
+                }
+            }
+        }
+    };
+    if stop {
+        // 运行时音频控制占用着 FFmpeg 的 stdin，交还后才能发送 `q`
+        if let Some(control) = &control {
+            control.release(&mut child);
+        }
+        ffmpeg_encoder::stop_gracefully(&mut child, ffmpeg_encoder::STOP_TIMEOUT).await?;
+    }
+
+    // 崩溃安全模式：将分片文件重封装为最终 MP4
+    if let Some(partial) = recovery::partial_output(&config) {
+        let container = config.container().unwrap_or(Container::Mp4);
+        recovery::finalize(&config.ffmpeg, &partial, &config.output, container)?;
+    }
+    if let Some(e) = aborted {
+        warn!("已录制的内容保存在 {}", config.output);
+        return Err(e.into());
+    }
+    loudness::normalize(&config)?;
 
     info!("✅ 录制完成: {}", config.output);
     Ok(())
 }
 
 fn build_ffmpeg_command(config: &RecorderConfig, input_format: &str, device: &str) -> Result<Command> {
-    let mut cmd = Command::new("ffmpeg");
+    let mut cmd = config.ffmpeg.command();
 
     // 输入参数
     cmd.arg("-f").arg(input_format);
     cmd.arg("-framerate").arg(config.fps.to_string());
+    cmd.args(config.low_latency_input_args());
 
     // 指定采集尺寸，避免采集到超高分辨率导致编码或播放异常
     if let Ok((w, h)) = config.get_resolution_parts() {
@@ -61,54 +119,25 @@ fn build_ffmpeg_command(config: &RecorderConfig, input_format: &str, device: &st
 
     cmd.arg("-i").arg(device);
 
-    // 音频参数 (可选) - 在输入之后、编码参数之前添加音频输入
-    if config.audio_enabled {
-        let audio_dev = config.audio_device.clone().unwrap_or_else(|| "Microphone".to_string());
-        #[cfg(windows)]
-        {
-            if crate::ffmpeg_encoder::probe_audio_device(&audio_dev) {
-                cmd.arg("-f").arg("dshow");
-                cmd.arg("-i").arg(format!("audio=\"{}\"", audio_dev));
-            } else {
-                log::warn!("未检测到音频设备 '{}'，跳过音频采集", audio_dev);
-            }
-        }
-
-        #[cfg(target_os = "linux")]
-        {
-            cmd.arg("-f").arg("pulse").arg("-i").arg(audio_dev);
-        }
-
-        #[cfg(target_os = "macos")]
-        {
-            if !audio_dev.is_empty() {
-                cmd.arg("-f").arg("avfoundation").arg("-i").arg(format!(":{}", audio_dev));
-            }
-        }
-    }
+    // 音频输入（可选）：麦克风、系统声音，位于视频输入之后
+    ffmpeg_encoder::push_audio_inputs(&mut cmd, config);
+    ffmpeg_encoder::push_video_inputs(&mut cmd, config);
 
-    // 视频缩放与像素格式
-    if let Ok((w, h)) = config.get_resolution_parts() {
-        cmd.arg("-vf").arg(format!("scale={}:{}:flags=lanczos,format=yuv420p", w, h));
-    } else {
-        cmd.arg("-vf").arg("format=yuv420p");
-    }
-
-    // map codec
-    let codec_name = match config.codec.as_str() {
-        "h264" => "libx264",
-        "h265" => "libx265",
-        other => other,
-    };
+    // 视频（遮挡、缩放与像素格式、画中画、文字叠加、水印）与音频（混音/分轨）共用一个滤镜图
+    let mut graph = FilterGraph::default();
+    let video = filter_graph::video(config, &mut graph, VideoSource::Device);
+    let audio = ffmpeg_encoder::compose_audio(config, &mut graph, 1);
+    ffmpeg_encoder::push_graph(&mut cmd, &graph, Some(&video), &audio);
 
     // 视频编码参数
-    cmd.arg("-c:v").arg(codec_name);
-    cmd.arg("-b:v").arg(&config.bitrate);
-    cmd.arg("-preset").arg("medium");
+    cmd.arg("-c:v").arg(config.codec.encoder());
+    cmd.args(config.video_encoder_args(false));
+
+    // 音轨元数据与音频编码参数（如果启用）
+    ffmpeg_encoder::push_audio_encoder(&mut cmd, config);
 
-    // 其他参数
-    cmd.arg("-y") // 覆盖输出文件
-        .arg(&config.output);
+    // 输出容器与路径（覆盖已存在的文件）
+    ffmpeg_encoder::push_output(&mut cmd, config);
 
     Ok(cmd)
 }
@@ -1,39 +1,43 @@
+use crate::audio_control::AudioControl;
 use crate::config::{RecorderConfig, StreamProtocol};
+use crate::container::Container;
+use crate::{ffmpeg_encoder, loudness, recovery};
+use crate::filter_graph::{self, FilterGraph, VideoSource};
+use crate::level_meter::LevelMeter;
 use anyhow::Result;
-use log::{info, warn};
+use log::{error, info, warn};
 use std::process::{Command, Stdio};
 use std::time::Duration;
 use tokio::time::sleep;
-use std::io::{BufRead, BufReader};
 
 pub async fn start_streaming(config: RecorderConfig) -> Result<()> {
     info!("🌐 开始推流到: {}", config.output);
+    // --stream 也可写入本地文件，此时同样支持崩溃安全模式
+    recovery::warn_if_unsupported(&config);
 
-    let input_format = config.get_input_format();
-    let device = config.get_capture_device();
-
-    // 构建推流命令
-    let mut ffmpeg_cmd = build_streaming_command(&config, input_format, &device)?;
+    // 构建推流命令（纯音频模式不采集屏幕）
+    let mut ffmpeg_cmd = if config.audio_only {
+        ffmpeg_encoder::build_audio_only_command(&config)
+    } else {
+        let input_format = config.get_input_format();
+        let device = config.get_capture_device();
+        build_streaming_command(&config, input_format, &device)?
+    };
 
     info!("📝 FFmpeg 推流命令: {:?}", ffmpeg_cmd);
 
     // 执行 FFmpeg
+    // stdin 供运行时音频控制向 FFmpeg 发送滤镜命令
     let mut child = ffmpeg_cmd
+        .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()?;
 
-    // 将 FFmpeg stderr 打印到日志，便于调试连接错误
-    if let Some(stderr) = child.stderr.take() {
-        std::thread::spawn(move || {
-            let reader = BufReader::new(stderr);
-            for line in reader.lines() {
-                if let Ok(l) = line {
-                    log::info!("ffmpeg | {}", l);
-                }
-            }
-        });
-    }
+    // 将 FFmpeg stderr 打印到日志，便于调试连接错误；电平统计行交给电平监测
+    let meter = LevelMeter::new(&config);
+    ffmpeg_encoder::log_stderr(&mut child, meter.clone());
+    let control = AudioControl::start(&config, &mut child, meter.clone());
 
     // 推流监控
     let start_time = std::time::Instant::now();
@@ -42,49 +46,69 @@ pub async fn start_streaming(config: RecorderConfig) -> Result<()> {
     if config.duration > 0 {
         let duration = Duration::from_secs(config.duration as u64);
         info!("⏱️  推流时间限制: {:?}", duration);
-
-        loop {
-            tokio::select! {
-                _ = sleep(duration) => {
-                    info!("⏹️  停止推流...");
-                    child.kill()?;
-                    break;
-                }
-                _ = stats_interval.tick() => {
-                    let elapsed = start_time.elapsed();
-                    info!("📊 推流统计: 已运行 {:?}", elapsed);
-                }
-                _ = async { child.wait() } => {
-                    break;
-                }
-            }
-        }
     } else {
         info!("⏳ 无时间限制，按 Ctrl+C 停止推流");
-        loop {
-            tokio::select! {
-                _ = stats_interval.tick() => {
-                    let elapsed = start_time.elapsed();
-                    info!("📊 推流统计: 已运行 {:?}", elapsed);
+    }
+    let deadline = (config.duration > 0)
+        .then(|| start_time + Duration::from_secs(config.duration as u64));
+
+    // 静音超时终止时与录制一致，先正常结束 FFmpeg，保留已写入的内容后再返回错误
+    let mut aborted = None;
+    let stop = loop {
+        tokio::select! {
+            _ = stats_interval.tick() => {
+                let elapsed = start_time.elapsed();
+                info!("📊 推流统计: 已运行 {:?}", elapsed);
+            }
+            _ = sleep(Duration::from_millis(200)) => {
+                if child.try_wait()?.is_some() {
+                    break false;
+                }
+                if deadline.is_some_and(|d| std::time::Instant::now() >= d) {
+                    info!("⏹️  停止推流...");
+                    break true;
                 }
-                status = async { child.wait() } => {
-                    let _ = status?;
-                    break;
+                if let Some(Err(e)) = meter.as_ref().map(|m| m.poll()) {
+                    error!("🔇 {}", e);
+                    aborted = Some(e);
+                    break true;
                 }
             }
         }
+    };
+    if stop {
+        // 运行时音频控制占用着 FFmpeg 的 stdin，交还后才能发送 `q`，写入文件时才能写完尾部
+        if let Some(control) = &control {
+            control.release(&mut child);
+        }
+        ffmpeg_encoder::stop_gracefully(&mut child, ffmpeg_encoder::STOP_TIMEOUT).await?;
     }
 
+    // 崩溃安全模式：将分片文件重封装为最终 MP4
+    if let Some(partial) = recovery::partial_output(&config) {
+        let container = config.container().unwrap_or(Container::Mp4);
+        recovery::finalize(&config.ffmpeg, &partial, &config.output, container)?;
+    }
+    if let Some(e) = aborted {
+        if config.protocol == StreamProtocol::File {
+            warn!("已录制的内容保存在 {}", config.output);
+        }
+        return Err(e.into());
+    }
+    // --stream 写入本地文件时同样在结束后做响度标准化，推流地址由 normalize 跳过
+    loudness::normalize(&config)?;
+
     info!("✅ 推流已停止");
     Ok(())
 }
 
 fn build_streaming_command(config: &RecorderConfig, input_format: &str, device: &str) -> Result<Command> {
-    let mut cmd = Command::new("ffmpeg");
+    let mut cmd = config.ffmpeg.command();
 
     // 输入参数
     cmd.arg("-f").arg(input_format);
     cmd.arg("-framerate").arg(config.fps.to_string());
+    cmd.args(config.low_latency_input_args());
 
     // 为 gdigrab/x11grab 指定采集尺寸，避免高分辨率原始输入导致编码或播放异常
     if let Ok((w, h)) = config.get_resolution_parts() {
@@ -99,81 +123,25 @@ fn build_streaming_command(config: &RecorderConfig, input_format: &str, device:
 
     cmd.arg("-i").arg(device);
 
-    // 音频参数（可选）：当用户启用音频时，尝试探测并添加音频输入
-    if config.audio_enabled {
-        let audio_dev = config.audio_device.clone().unwrap_or_else(|| "Microphone".to_string());
-        // Windows 使用 dshow
-        #[cfg(windows)]
-        {
-            if crate::ffmpeg_encoder::probe_audio_device(&audio_dev) {
-                cmd.arg("-f").arg("dshow")
-                    .arg("-i")
-                    .arg(format!("audio=\"{}\"", audio_dev));
-            } else {
-                warn!("未检测到音频设备 '{}'，将跳过音频采集", audio_dev);
-            }
-        }
-
-        // Linux: 使用 PulseAudio 的 default 名称（如需自定义，传入 audio_device）
-        #[cfg(target_os = "linux")]
-        {
-            let dev = audio_dev.clone();
-            // 简单尝试，假设 PulseAudio/ALSA 可用；未探测则仍加入
-            cmd.arg("-f").arg("pulse").arg("-i").arg(dev);
-        }
-
-        // macOS: avfoundation 需要索引或名称，如用户提供则尝试使用
-        #[cfg(target_os = "macos")]
-        {
-            // macOS 音频输入通常为 avfoundation 索引，用户需传入正确值
-            if !audio_dev.is_empty() {
-                cmd.arg("-f").arg("avfoundation").arg("-i").arg(format!(":{}", audio_dev));
-            }
-        }
-    }
-
-    // 视频缩放与像素格式（在所有输入之后，编码参数之前）
-    if let Ok((w, h)) = config.get_resolution_parts() {
-        // 使用 filter 同时设定分辨率与像素格式，避免编码器使用不兼容的色彩空间
-        cmd.arg("-vf").arg(format!("scale={}:{}:flags=lanczos,format=yuv420p", w, h));
-    } else {
-        // 即使未能获取分辨率，也强制像素格式
-        cmd.arg("-vf").arg("format=yuv420p");
-    }
+    // 音频输入（可选）：麦克风、系统声音，位于视频输入之后
+    ffmpeg_encoder::push_audio_inputs(&mut cmd, config);
+    ffmpeg_encoder::push_video_inputs(&mut cmd, config);
 
-    // map common codec names to ffmpeg encoder names
-    let codec_name = match config.codec.as_str() {
-        "h264" => "libx264",
-        "h265" => "libx265",
-        other => other,
-    };
+    // 视频（遮挡、缩放与像素格式、画中画、文字叠加、水印）与音频（混音/分轨）共用一个滤镜图
+    let mut graph = FilterGraph::default();
+    let video = filter_graph::video(config, &mut graph, VideoSource::Device);
+    let audio = ffmpeg_encoder::compose_audio(config, &mut graph, 1);
+    ffmpeg_encoder::push_graph(&mut cmd, &graph, Some(&video), &audio);
 
     // 视频编码参数（优化推流）
-    cmd.arg("-c:v").arg(codec_name);
-    cmd.arg("-b:v").arg(&config.bitrate);
-    cmd.arg("-preset").arg("veryfast"); // 推流使用更快的预设
-    cmd.arg("-maxrate").arg(&config.bitrate);
-    cmd.arg("-bufsize").arg(&config.bitrate);
+    cmd.arg("-c:v").arg(config.codec.encoder());
+    cmd.args(config.video_encoder_args(true)); // 推流使用更快的预设
+
+    // 音轨元数据与音频编码参数（如果启用）
+    ffmpeg_encoder::push_audio_encoder(&mut cmd, config);
 
     // 根据协议添加不同的输出相关参数
-    match config.protocol {
-        StreamProtocol::RTMP => {
-            // RTMP 使用 FLV 容器
-            cmd.arg("-flvflags").arg("no_duration_filesize")
-                .arg("-rtmp_live").arg("live")
-                .arg(config.output.clone());
-        }
-        StreamProtocol::RTSP => {
-            // RTSP 推流，使用 TCP 传输以提高可靠性
-            cmd.arg("-rtsp_transport").arg("tcp");
-            cmd.arg("-f").arg("rtsp");
-            cmd.arg(config.output.clone());
-        }
-        StreamProtocol::File => {
-            // 文件输出
-            cmd.arg("-y").arg(config.output.clone());
-        }
-    }
+    ffmpeg_encoder::push_output(&mut cmd, config);
 
     Ok(cmd)
 }
@@ -0,0 +1,278 @@
+/// 编码器调优参数：预设、tune、GOP、profile/level、B 帧与线程数，按编码器校验并映射
+use std::fmt;
+use std::str::FromStr;
+
+use crate::codec::{CodecFamily, VideoCodec};
+use crate::error::RecorderError;
+use crate::validation::ValidationReport;
+
+/// 推流时默认的关键帧间隔（秒），RTMP 平台普遍要求 2 秒
+pub const STREAM_GOP_SECONDS: f64 = 2.0;
+
+/// 低延迟模式的关键帧间隔（秒），缩短新观众/丢包后的恢复时间
+pub const LOW_LATENCY_GOP_SECONDS: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tune {
+    /// 零延迟：关闭前瞻与帧重排，用于实时推流
+    ZeroLatency,
+    /// 静态画面：适合幻灯片、文档等变化很少的屏幕内容
+    StillImage,
+    Film,
+    Animation,
+    Grain,
+    FastDecode,
+}
+
+impl Tune {
+    pub const ALL: [Tune; 6] = [
+        Tune::ZeroLatency,
+        Tune::StillImage,
+        Tune::Film,
+        Tune::Animation,
+        Tune::Grain,
+        Tune::FastDecode,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tune::ZeroLatency => "zerolatency",
+            Tune::StillImage => "stillimage",
+            Tune::Film => "film",
+            Tune::Animation => "animation",
+            Tune::Grain => "grain",
+            Tune::FastDecode => "fastdecode",
+        }
+    }
+
+    pub fn supported_by(&self, codec: VideoCodec) -> bool {
+        self.encoder_args(codec).is_some()
+    }
+
+    /// 映射为编码器参数，编码器不支持该 tune 时返回 None
+    fn encoder_args(&self, codec: VideoCodec) -> Option<Vec<&'static str>> {
+        match (codec, self) {
+            (VideoCodec::H264, _) => Some(vec!["-tune", self.name()]),
+            (VideoCodec::H265, Tune::StillImage | Tune::Film) => None,
+            (VideoCodec::H265, _) => Some(vec!["-tune", self.name()]),
+            (VideoCodec::H264Nvenc | VideoCodec::HevcNvenc, Tune::ZeroLatency) => {
+                Some(vec!["-tune", "ull", "-zerolatency", "1"])
+            }
+            (VideoCodec::Vp9 | VideoCodec::Av1Aom, Tune::ZeroLatency) => Some(vec!["-lag-in-frames", "0"]),
+            (VideoCodec::Vp9, Tune::StillImage) => Some(vec!["-tune-content", "screen"]),
+            (VideoCodec::Vp9, Tune::Film | Tune::Grain) => Some(vec!["-tune-content", "film"]),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Tune {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Tune {
+    type Err = RecorderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        Tune::ALL.into_iter().find(|t| t.name() == lower).ok_or_else(|| {
+            RecorderError::InvalidEncoderOption(format!(
+                "未知 tune {}，可选 zerolatency, stillimage, film, animation, grain, fastdecode",
+                s
+            ))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EncoderTuning {
+    /// 编码速度预设，未指定时录制偏画质、推流偏速度
+    pub preset: Option<String>,
+    pub tune: Option<Tune>,
+    /// 关键帧间隔（秒），未指定时推流默认 2 秒，录制使用编码器默认值
+    pub gop_seconds: Option<f64>,
+    pub profile: Option<String>,
+    pub level: Option<String>,
+    /// B 帧数量
+    pub bframes: Option<u32>,
+    /// 编码线程数（0 表示自动）
+    pub threads: Option<u32>,
+}
+
+impl EncoderTuning {
+    /// 按编码器检查各项调优参数，问题全部记入报告
+    pub fn validate(&self, codec: VideoCodec, report: &mut ValidationReport) {
+        if let Some(preset) = &self.preset {
+            let presets = codec.presets();
+            if !presets.contains(&preset.as_str()) {
+                report
+                    .error("preset", preset, format!("{} 不支持该预设", codec))
+                    .suggest(format!("可选: {}", presets.join(", ")));
+            }
+        }
+
+        if let Some(tune) = self.tune {
+            if !tune.supported_by(codec) {
+                let supported: Vec<&str> =
+                    Tune::ALL.iter().filter(|t| t.supported_by(codec)).map(|t| t.name()).collect();
+                let issue = report.error("tune", tune, format!("{} 不支持该 tune", codec));
+                if supported.is_empty() {
+                    issue.suggest("去掉 --tune");
+                } else {
+                    issue.suggest(format!("可选: {}", supported.join(", ")));
+                }
+            }
+        }
+
+        if let Some(gop) = self.gop_seconds {
+            if !(gop > 0.0 && gop <= 20.0) {
+                report
+                    .error("gop", gop, "关键帧间隔超出范围")
+                    .suggest("取 0-20 秒之间的值，推流通常为 2");
+            }
+        }
+
+        if let Some(profile) = &self.profile {
+            let profiles = profiles(codec.family());
+            if !profiles.contains(&profile.as_str()) {
+                report
+                    .error("profile", profile, format!("{} 不支持该 profile（当前输出为 8 位 yuv420p）", codec))
+                    .suggest(format!("可选: {}", profiles.join(", ")));
+            }
+        }
+
+        if let Some(level) = &self.level {
+            let levels = levels(codec.family());
+            if !levels.contains(&level.as_str()) {
+                let issue = report.error("level", level, format!("{} 不支持该 level", codec));
+                if levels.is_empty() {
+                    issue.suggest("该编码器不支持设置 level，去掉 --level");
+                } else {
+                    issue.suggest(format!("可选: {}", levels.join(", ")));
+                }
+            }
+        }
+
+        if let Some(bf) = self.bframes {
+            let max = codec.max_bframes();
+            if bf > max {
+                if max == 0 {
+                    report
+                        .error("bframes", bf, format!("{} 不支持 B 帧", codec))
+                        .suggest("去掉 --bframes 或设为 0");
+                } else {
+                    report
+                        .error("bframes", bf, format!("{} 的 B 帧数量超出范围", codec))
+                        .suggest(format!("取 0-{} 之间的值", max));
+                }
+            }
+        }
+
+        if let Some(threads) = self.threads {
+            if threads > 64 {
+                report
+                    .error("threads", threads, "线程数超出范围")
+                    .suggest("取 0-64 之间的值，0 表示自动");
+            }
+        }
+    }
+
+    /// 生成调优相关的编码器参数
+    ///
+    /// `realtime` 决定未指定预设时的默认速度，`default_gop` 为未指定 GOP 时使用的关键帧间隔。
+    pub fn encoder_args(
+        &self,
+        codec: VideoCodec,
+        fps: u32,
+        realtime: bool,
+        default_gop: Option<f64>,
+    ) -> Vec<String> {
+        let mut args: Vec<String> = codec
+            .speed_args(self.preset.as_deref(), realtime)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        let mut push = |k: &str, v: String| {
+            args.push(k.to_string());
+            args.push(v);
+        };
+
+        if let Some(tune) = self.tune {
+            for pair in tune.encoder_args(codec).unwrap_or_default().chunks(2) {
+                push(pair[0], pair[1].to_string());
+            }
+        }
+
+        if let Some(gop) = self.gop_seconds.or(default_gop) {
+            let frames = ((fps as f64) * gop).round().max(1.0) as u32;
+            push("-g", frames.to_string());
+            push("-keyint_min", frames.to_string());
+            // 按时间强制关键帧，对所有编码器生效且不受场景切换影响
+            push("-force_key_frames", format!("expr:gte(t,n_forced*{})", gop));
+        }
+
+        if let Some(profile) = &self.profile {
+            push("-profile:v", profile.clone());
+        }
+        if let Some(level) = &self.level {
+            let (key, value) = level_option(codec, level);
+            push(key, value);
+        }
+        if let Some(bf) = self.bframes {
+            // VP9/AV1 没有 B 帧，validate 已保证此时为 0
+            if codec.max_bframes() > 0 {
+                push("-bf", bf.to_string());
+            }
+        }
+        if let Some(threads) = self.threads {
+            push("-threads", threads.to_string());
+        }
+
+        args
+    }
+}
+
+/// 各编码器设置 level 的方式
+///
+/// x264 与 NVENC 的私有 level 选项接受 `4.1` 写法；x265 没有该选项，需经 x265-params 传入；
+/// QSV 使用通用的整数 level，按 主版本×10+次版本 表示（4.1 为 41，4 为 40），1b 为 9。
+fn level_option(codec: VideoCodec, level: &str) -> (&'static str, String) {
+    match codec {
+        VideoCodec::H265 => ("-x265-params", format!("level-idc={}", level)),
+        VideoCodec::H264Qsv => {
+            let value = if level == "1b" {
+                9
+            } else {
+                let (major, minor) = level.split_once('.').unwrap_or((level, "0"));
+                major.parse::<u32>().unwrap_or(0) * 10 + minor.parse::<u32>().unwrap_or(0)
+            };
+            ("-level", value.to_string())
+        }
+        _ => ("-level", level.to_string()),
+    }
+}
+
+/// 各编码格式在 8 位 yuv420p 下可用的 profile
+fn profiles(family: CodecFamily) -> &'static [&'static str] {
+    match family {
+        CodecFamily::H264 => &["baseline", "main", "high"],
+        CodecFamily::Hevc => &["main"],
+        CodecFamily::Vp9 => &["0"],
+        CodecFamily::Av1 => &["main"],
+    }
+}
+
+fn levels(family: CodecFamily) -> &'static [&'static str] {
+    match family {
+        CodecFamily::H264 => &[
+            "1", "1b", "1.1", "1.2", "1.3", "2", "2.1", "2.2", "3", "3.1", "3.2", "4", "4.1", "4.2",
+            "5", "5.1", "5.2", "6", "6.1", "6.2",
+        ],
+        CodecFamily::Hevc => &[
+            "1", "2", "2.1", "3", "3.1", "4", "4.1", "5", "5.1", "5.2", "6", "6.1", "6.2",
+        ],
+        CodecFamily::Vp9 | CodecFamily::Av1 => &[],
+    }
+}
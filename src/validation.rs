@@ -0,0 +1,197 @@
+/// 配置校验报告：一次性收集全部问题（字段、取值、原因、建议），
+/// 以文本形式展示给用户，或以 JSON 形式输出给脚本与工具
+use std::fmt;
+use std::str::FromStr;
+
+use log::{error, warn};
+use serde::Serialize;
+
+use crate::error::RecorderError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// 无法开始录制
+    Error,
+    /// 可以继续，但结果可能不符合预期（包括已自动修正的项）
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    /// 对应的配置项（与命令行参数同名）
+    pub field: String,
+    /// 出错的取值
+    pub value: String,
+    pub reason: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+}
+
+impl ValidationIssue {
+    pub fn suggest(&mut self, suggestion: impl Into<String>) -> &mut Self {
+        self.suggestion = Some(suggestion.into());
+        self
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {}: {}", self.field, self.value, self.reason)?;
+        if let Some(s) = &self.suggestion {
+            write!(f, "（建议: {}）", s)?;
+        }
+        Ok(())
+    }
+}
+
+/// 报告的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            other => Err(format!("未知的报告格式 {}，可选 text, json", other)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn error(&mut self, field: &str, value: impl fmt::Display, reason: impl Into<String>) -> &mut ValidationIssue {
+        self.push(Severity::Error, field, value, reason)
+    }
+
+    pub fn warning(&mut self, field: &str, value: impl fmt::Display, reason: impl Into<String>) -> &mut ValidationIssue {
+        self.push(Severity::Warning, field, value, reason)
+    }
+
+    /// 将错误类型记录为一条错误，建议取自错误本身
+    pub fn reject(&mut self, field: &str, value: impl fmt::Display, err: RecorderError) -> &mut ValidationIssue {
+        let suggestion = err.suggestion();
+        let issue = self.error(field, value, err.to_string());
+        issue.suggestion = suggestion;
+        issue
+    }
+
+    /// 解析命令行取值，失败时记录错误并返回 None，以便继续检查其余参数
+    pub fn parse<T>(&mut self, field: &str, value: &str) -> Option<T>
+    where
+        T: FromStr<Err = RecorderError>,
+    {
+        match value.parse() {
+            Ok(v) => Some(v),
+            Err(e) => {
+                self.reject(field, value, e);
+                None
+            }
+        }
+    }
+
+    /// 并入另一份报告，其中的错误一律降级为警告，原因后附上说明
+    pub fn merge_as_warnings(&mut self, other: ValidationReport, note: &str) {
+        self.issues.extend(other.issues.into_iter().map(|mut issue| {
+            if issue.severity == Severity::Error {
+                issue.severity = Severity::Warning;
+                issue.reason = format!("{}（{}）", issue.reason, note);
+            }
+            issue
+        }));
+    }
+
+    fn push(
+        &mut self,
+        severity: Severity,
+        field: &str,
+        value: impl fmt::Display,
+        reason: impl Into<String>,
+    ) -> &mut ValidationIssue {
+        self.issues.push(ValidationIssue {
+            severity,
+            field: field.to_string(),
+            value: value.to_string(),
+            reason: reason.into(),
+            suggestion: None,
+        });
+        self.issues.last_mut().expect("刚刚写入")
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.issues.iter().filter(|i| i.severity == Severity::Error).count()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.error_count() == 0
+    }
+
+    pub fn to_json(&self) -> String {
+        #[derive(Serialize)]
+        struct JsonReport<'a> {
+            valid: bool,
+            errors: usize,
+            warnings: usize,
+            issues: &'a [ValidationIssue],
+        }
+
+        let errors = self.error_count();
+        let report = JsonReport {
+            valid: errors == 0,
+            errors,
+            warnings: self.issues.len() - errors,
+            issues: &self.issues,
+        };
+        serde_json::to_string_pretty(&report).expect("校验报告总能序列化为 JSON")
+    }
+
+    /// 按指定格式输出报告；存在错误时返回 `InvalidConfig`
+    ///
+    /// 文本格式通过日志输出；JSON 格式无论成败都写到标准输出，日志仍在标准错误。
+    pub fn finish(&self, format: ReportFormat) -> Result<(), RecorderError> {
+        match format {
+            ReportFormat::Json => println!("{}", self.to_json()),
+            ReportFormat::Text if self.is_valid() => {
+                for issue in &self.issues {
+                    warn!("⚠️  {}", issue);
+                }
+            }
+            ReportFormat::Text => error!("❌ {}", self),
+        }
+
+        match self.error_count() {
+            0 => Ok(()),
+            n => Err(RecorderError::InvalidConfig(n)),
+        }
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    /// 多行文本，错误在前、警告在后
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors = self.error_count();
+        write!(f, "配置校验发现 {} 个错误、{} 个警告", errors, self.issues.len() - errors)?;
+
+        for severity in [Severity::Error, Severity::Warning] {
+            for issue in self.issues.iter().filter(|i| i.severity == severity) {
+                let mark = if severity == Severity::Error { "✗" } else { "!" };
+                write!(f, "\n  {} {} = {}\n      原因: {}", mark, issue.field, issue.value, issue.reason)?;
+                if let Some(s) = &issue.suggestion {
+                    write!(f, "\n      建议: {}", s)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
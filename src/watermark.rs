@@ -0,0 +1,185 @@
+/// 图片水印（PNG 徽标）
+///
+/// FFmpeg 采集时水印图片作为额外的循环输入，经缩放与透明度调整后用 overlay 叠加到缩放后的画面上；
+/// 原生捕获直接在 BGRA 帧上合成，启动时解码并缩放一次，逐帧只做 alpha 混合。
+use anyhow::{Context, Result};
+use image::imageops::{self, FilterType};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+use crate::ffmpeg_encoder::FfmpegCapabilities;
+use crate::filter_graph::{FilterGraph, Pad};
+use crate::overlay::OverlayPosition;
+use crate::validation::ValidationReport;
+
+/// 允许的缩放倍数（相对图片原始尺寸）
+pub const SCALE_RANGE: RangeInclusive<f64> = 0.05..=4.0;
+/// 水印到画面边缘的最大距离（像素）
+const MAX_MARGIN: u32 = 1000;
+
+#[derive(Debug, Clone)]
+pub struct Watermark {
+    /// 水印图片（PNG，透明部分保持透明）
+    pub path: PathBuf,
+    pub position: OverlayPosition,
+    /// 到画面边缘的距离（像素）
+    pub margin: u32,
+    /// 相对图片原始尺寸的缩放倍数
+    pub scale: f64,
+    /// 不透明度，0 为完全透明，1 为原样
+    pub opacity: f64,
+}
+
+impl Watermark {
+    /// 需要的 FFmpeg 滤镜；不透明度小于 1 时用 colorchannelmixer 调整 alpha
+    fn required_filters(&self) -> Vec<&'static str> {
+        let mut filters = vec!["overlay", "scale"];
+        if self.opacity < 1.0 {
+            filters.push("colorchannelmixer");
+        }
+        filters
+    }
+
+    /// 额外的输入参数，图片循环为连续的视频流
+    pub fn input_args(&self) -> Vec<String> {
+        vec!["-loop".to_string(), "1".to_string(), "-i".to_string(), self.path.display().to_string()]
+    }
+
+    /// 将第 `input` 路输入的水印叠加到 `main` 上，返回合成后的端口
+    ///
+    /// 水印为无限循环的图片，shortest=1 使画面结束时滤镜图随之结束。
+    pub fn compose(&self, graph: &mut FilterGraph, main: &Pad, input: usize) -> Pad {
+        let mut filters = vec![
+            format!("scale=trunc(iw*{0}):trunc(ih*{0})", self.scale),
+            "format=rgba".to_string(),
+        ];
+        if self.opacity < 1.0 {
+            filters.push(format!("colorchannelmixer=aa={}", self.opacity));
+        }
+        let watermark = graph.link(&[&Pad::video_input(input)], &filters, "wm");
+
+        let x = if self.position.is_left() {
+            self.margin.to_string()
+        } else {
+            format!("W-w-{}", self.margin)
+        };
+        let y = if self.position.is_top() {
+            self.margin.to_string()
+        } else {
+            format!("H-h-{}", self.margin)
+        };
+        let overlay = format!("overlay=x={}:y={}:shortest=1", x, y);
+        graph.link(&[main, &watermark], &[overlay, "format=yuv420p".to_string()], "v")
+    }
+
+    pub fn validate(&self, ffmpeg: &FfmpegCapabilities, report: &mut ValidationReport) {
+        let path = self.path.display();
+        match image::image_dimensions(&self.path) {
+            Ok((w, h)) if (w as f64 * self.scale) < 1.0 || (h as f64 * self.scale) < 1.0 => {
+                report.error("watermark_scale", self.scale, format!("缩放后水印不足 1 像素（原始 {}x{}）", w, h));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                report
+                    .error("watermark", &path, format!("无法读取水印图片: {}", e))
+                    .suggest("使用带透明通道的 PNG 图片");
+            }
+        }
+        if !SCALE_RANGE.contains(&self.scale) {
+            report
+                .error("watermark_scale", self.scale, "缩放倍数超出允许范围")
+                .suggest(format!("取 {} - {}", SCALE_RANGE.start(), SCALE_RANGE.end()));
+        }
+        if !(0.0..=1.0).contains(&self.opacity) {
+            report
+                .error("watermark_opacity", self.opacity, "不透明度超出允许范围")
+                .suggest("取 0 - 1，例如 0.7");
+        }
+        if self.margin > MAX_MARGIN {
+            report
+                .error("watermark_margin", self.margin, "边距过大")
+                .suggest(format!("取 0 - {} 像素", MAX_MARGIN));
+        }
+        for filter in self.required_filters() {
+            if let Err(e) = ffmpeg.ensure_filter(filter) {
+                report.reject("watermark", filter, e);
+            }
+        }
+    }
+
+    /// 解码并缩放图片，预乘不透明度，供原生捕获逐帧合成
+    pub fn load(&self) -> Result<WatermarkImage> {
+        let rgba = image::open(&self.path)
+            .with_context(|| format!("无法读取水印图片 {}", self.path.display()))?
+            .to_rgba8();
+        let width = ((rgba.width() as f64 * self.scale) as u32).max(1);
+        let height = ((rgba.height() as f64 * self.scale) as u32).max(1);
+        let rgba = if (width, height) == rgba.dimensions() {
+            rgba
+        } else {
+            imageops::resize(&rgba, width, height, FilterType::Lanczos3)
+        };
+
+        // 转为预乘 alpha 的 BGRA，与捕获帧的像素排列一致
+        let pixels = rgba
+            .pixels()
+            .flat_map(|p| {
+                let [r, g, b, a] = p.0;
+                let alpha = (a as f64 * self.opacity).round() as u32;
+                let premultiply = |c: u8| ((c as u32 * alpha + 127) / 255) as u8;
+                [premultiply(b), premultiply(g), premultiply(r), alpha as u8]
+            })
+            .collect();
+        Ok(WatermarkImage {
+            pixels,
+            width: width as usize,
+            height: height as usize,
+            position: self.position,
+            margin: self.margin as usize,
+        })
+    }
+}
+
+/// 已解码的水印（预乘 alpha 的 BGRA）
+pub struct WatermarkImage {
+    pixels: Vec<u8>,
+    width: usize,
+    height: usize,
+    position: OverlayPosition,
+    margin: usize,
+}
+
+impl WatermarkImage {
+    /// 混合到 BGRA 帧上，`stride` 为每行字节数；超出画面的部分被裁掉
+    pub fn blend(&self, frame: &mut [u8], frame_width: usize, frame_height: usize, stride: usize) {
+        let x0 = if self.position.is_left() {
+            self.margin
+        } else {
+            frame_width.saturating_sub(self.width + self.margin)
+        };
+        let y0 = if self.position.is_top() {
+            self.margin
+        } else {
+            frame_height.saturating_sub(self.height + self.margin)
+        };
+        let cols = self.width.min(frame_width.saturating_sub(x0));
+        let rows = self.height.min(frame_height.saturating_sub(y0));
+
+        for row in 0..rows {
+            let src = &self.pixels[row * self.width * 4..][..cols * 4];
+            let start = (y0 + row) * stride + x0 * 4;
+            let Some(dst) = frame.get_mut(start..start + cols * 4) else {
+                break;
+            };
+            for (d, s) in dst.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+                let inverse = 255 - s[3] as u32;
+                if inverse == 255 {
+                    continue;
+                }
+                for c in 0..3 {
+                    d[c] = (s[c] as u32 + (d[c] as u32 * inverse + 127) / 255).min(255) as u8;
+                }
+            }
+        }
+    }
+}